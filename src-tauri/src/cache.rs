@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const CACHE_DIR_NAME: &str = "substack-downloader-cache";
+pub const DEFAULT_CACHE_TTL_HOURS: u64 = 24;
+
+/// On-disk cache of fetched post HTML, one file per URL and session, so re-running
+/// an export with different settings doesn't hit the network again.
+#[derive(Debug, Clone)]
+pub struct HtmlCache {
+    dir: PathBuf,
+    ttl: Duration,
+    refresh: bool,
+    /// Hash of the session cookie the pages are fetched with; a page fetched as a
+    /// subscriber is never served to another session or to an anonymous one.
+    session: Option<u64>,
+}
+
+impl HtmlCache {
+    /// Opens the cache in the system temp directory. With `refresh` set, cached
+    /// entries are ignored but still overwritten by the fresh download. The
+    /// directory is private to the user, as it may hold subscriber-only posts.
+    pub fn open(ttl_hours: u64, refresh: bool, session_cookie: Option<&str>) -> Result<Self> {
+        let dir = std::env::temp_dir().join(CACHE_DIR_NAME).join("html");
+        fs::create_dir_all(&dir).context("Failed creating HTML cache directory.")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+                .context("Failed restricting HTML cache directory.")?;
+        }
+        Ok(Self {
+            dir,
+            ttl: Duration::from_secs(ttl_hours.saturating_mul(3600)),
+            refresh,
+            session: session_cookie.map(str::trim).filter(|cookie| !cookie.is_empty()).map(cache_key),
+        })
+    }
+
+    pub fn get(&self, url: &str) -> Option<String> {
+        if self.refresh {
+            return None;
+        }
+        let path = self.entry_path(url);
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        if age > self.ttl {
+            return None;
+        }
+        fs::read_to_string(path).ok()
+    }
+
+    pub fn put(&self, url: &str, html: &str) -> Result<()> {
+        fs::write(self.entry_path(url), html).context("Failed writing HTML cache entry.")
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        match self.session {
+            Some(session) => self.dir.join(format!("{:016x}-{session:016x}.html", cache_key(url))),
+            None => self.dir.join(format!("{:016x}.html", cache_key(url))),
+        }
+    }
+}
+
+/// Last copy of each feed with the validators it was served with, so the next
+/// load can ask Substack whether the feed changed instead of downloading it again.
+#[derive(Debug, Clone)]
+pub struct FeedCache {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedFeed {
+    pub etag: Option<String>,
+    /// The `Last-Modified` header, or the feed's own `<lastBuildDate>` without one.
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+impl FeedCache {
+    pub fn open() -> Result<Self> {
+        let dir = std::env::temp_dir().join(CACHE_DIR_NAME).join("feeds");
+        fs::create_dir_all(&dir).context("Failed creating feed cache directory.")?;
+        Ok(Self { dir })
+    }
+
+    pub fn get(&self, feed_url: &str) -> Option<CachedFeed> {
+        let raw = fs::read_to_string(self.entry_path(feed_url)).ok()?;
+        serde_json::from_str(&raw).ok()
+    }
+
+    pub fn put(&self, feed_url: &str, feed: &CachedFeed) -> Result<()> {
+        let raw = serde_json::to_string(feed)?;
+        fs::write(self.entry_path(feed_url), raw).context("Failed writing feed cache entry.")
+    }
+
+    fn entry_path(&self, feed_url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", cache_key(feed_url)))
+    }
+}
+
+/// FNV-1a hash of a URL or post id; unlike `DefaultHasher` it stays the same across
+/// Rust releases, so cache entries survive an app update.
+pub(crate) fn cache_key(url: &str) -> u64 {
+    url.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_entries_are_kept_per_session() {
+        let url = format!("https://example.substack.com/p/{}", uuid::Uuid::new_v4());
+        let anonymous = HtmlCache::open(1, false, None).unwrap();
+        let subscriber = HtmlCache::open(1, false, Some("substack.sid=abc")).unwrap();
+        let other = HtmlCache::open(1, false, Some("substack.sid=xyz")).unwrap();
+
+        anonymous.put(&url, "<p>Teaser</p>").unwrap();
+        assert_eq!(subscriber.get(&url), None);
+        subscriber.put(&url, "<p>Full post</p>").unwrap();
+        assert_eq!(subscriber.get(&url).as_deref(), Some("<p>Full post</p>"));
+        assert_eq!(anonymous.get(&url).as_deref(), Some("<p>Teaser</p>"));
+        assert_eq!(other.get(&url), None);
+
+        let _ = fs::remove_file(anonymous.entry_path(&url));
+        let _ = fs::remove_file(subscriber.entry_path(&url));
+    }
+}
@@ -0,0 +1,157 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{anyhow, Result};
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+const FONT_BYTES: &[u8] = include_bytes!("../assets/fonts/DejaVuSans-Bold.ttf");
+const COVER_WIDTH: u32 = 1000;
+const COVER_HEIGHT: u32 = 1500;
+const MARGIN: f32 = 90.0;
+const MAX_TITLE_LINES: usize = 5;
+pub const DEFAULT_COVER_BACKGROUND: Rgb<u8> = Rgb([38, 70, 83]);
+
+/// Renders a plain PNG cover: the title in the upper half and the author near
+/// the bottom, over a vertical gradient that darkens `background` downwards.
+pub fn generate_cover(title: &str, author: Option<&str>, background: Rgb<u8>) -> Result<Vec<u8>> {
+    let font = FontRef::try_from_slice(FONT_BYTES).map_err(|_| anyhow!("Bundled cover font is invalid."))?;
+    let mut canvas = RgbImage::from_fn(COVER_WIDTH, COVER_HEIGHT, |_, y| {
+        let shade = 1.0 - 0.45 * (y as f32 / COVER_HEIGHT as f32);
+        Rgb(background.0.map(|channel| (channel as f32 * shade) as u8))
+    });
+    let ink = if luminance(background) > 140.0 {
+        Rgb([24, 24, 24])
+    } else {
+        Rgb([250, 250, 246])
+    };
+    let max_width = COVER_WIDTH as f32 - 2.0 * MARGIN;
+
+    let mut size = 110.0;
+    let mut lines = wrap_text(&font, PxScale::from(size), title, max_width);
+    while lines.len() > MAX_TITLE_LINES && size > 48.0 {
+        size -= 8.0;
+        lines = wrap_text(&font, PxScale::from(size), title, max_width);
+    }
+    lines.truncate(MAX_TITLE_LINES);
+    let line_height = size * 1.2;
+    let block_height = line_height * lines.len() as f32;
+    let mut baseline = (COVER_HEIGHT as f32 * 0.4 - block_height / 2.0).max(MARGIN) + size;
+    for line in &lines {
+        draw_line(&mut canvas, &font, PxScale::from(size), line, baseline, ink);
+        baseline += line_height;
+    }
+
+    if let Some(author) = author.map(str::trim).filter(|author| !author.is_empty()) {
+        let scale = PxScale::from(54.0);
+        let author_baseline = COVER_HEIGHT as f32 - MARGIN * 1.5;
+        if let Some(line) = wrap_text(&font, scale, author, max_width).first() {
+            draw_line(&mut canvas, &font, scale, line, author_baseline, ink);
+        }
+    }
+
+    let mut out = std::io::Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(canvas).write_to(&mut out, ImageFormat::Png)?;
+    Ok(out.into_inner())
+}
+
+/// Parses `#rrggbb` (the `#` is optional).
+pub fn parse_hex_color(value: &str) -> Option<Rgb<u8>> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+fn luminance(color: Rgb<u8>) -> f32 {
+    let [r, g, b] = color.0.map(f32::from);
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+fn text_width(font: &FontRef<'_>, scale: PxScale, text: &str) -> f32 {
+    let scaled = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for ch in text.chars() {
+        let id = scaled.glyph_id(ch);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Greedy word wrap; a single word wider than the line is kept on its own line.
+fn wrap_text(font: &FontRef<'_>, scale: PxScale, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{current} {word}")
+        };
+        if !current.is_empty() && text_width(font, scale, &candidate) > max_width {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Draws one horizontally centered line with its baseline at `baseline`.
+fn draw_line(canvas: &mut RgbImage, font: &FontRef<'_>, scale: PxScale, text: &str, baseline: f32, ink: Rgb<u8>) {
+    let scaled = font.as_scaled(scale);
+    let mut x = ((canvas.width() as f32 - text_width(font, scale, text)) / 2.0).max(0.0);
+    let mut previous = None;
+    for ch in text.chars() {
+        let id = scaled.glyph_id(ch);
+        if let Some(previous) = previous {
+            x += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(x, baseline));
+        x += scaled.h_advance(id);
+        previous = Some(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= canvas.width() as i32 || py >= canvas.height() as i32 {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            for (channel, ink_channel) in pixel.0.iter_mut().zip(ink.0) {
+                *channel = (*channel as f32 * (1.0 - coverage) + ink_channel as f32 * coverage) as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_a_png_cover() {
+        let png = generate_cover("A Fairly Long Publication Title", Some("Jane Doe"), DEFAULT_COVER_BACKGROUND).unwrap();
+        let decoded = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (COVER_WIDTH, COVER_HEIGHT));
+        let ink_pixels = decoded.to_rgb8().pixels().filter(|pixel| pixel.0[0] > 200).count();
+        assert!(ink_pixels > 1000);
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_hex_color("#1a2B3c"), Some(Rgb([0x1a, 0x2b, 0x3c])));
+        assert_eq!(parse_hex_color("fff"), None);
+        assert_eq!(parse_hex_color("#12345g"), None);
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Broad category of a command failure, so the frontend can react differently
+/// to a bad URL, a flaky connection, or a page it could not understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    InvalidInput,
+    InvalidUrl,
+    Network,
+    NotFound,
+    Parse,
+    Io,
+    Unknown,
+}
+
+/// Error returned by the Tauri commands. Internal code keeps using `anyhow`;
+/// sites that know what went wrong wrap an `AppError` so the kind survives.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl AppError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retryable: kind == ErrorKind::Network,
+        }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidInput, message)
+    }
+
+    pub fn invalid_url(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidUrl, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Parse, message)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        let (kind, retryable) = classify(&error);
+        Self {
+            kind,
+            message: format!("{error:#}"),
+            retryable,
+        }
+    }
+}
+
+/// Picks the kind from the first error in the chain that carries one.
+fn classify(error: &anyhow::Error) -> (ErrorKind, bool) {
+    for cause in error.chain() {
+        if let Some(app_error) = cause.downcast_ref::<AppError>() {
+            return (app_error.kind, app_error.retryable);
+        }
+        if let Some(http_error) = cause.downcast_ref::<reqwest::Error>() {
+            return match http_error.status() {
+                Some(status) if status == reqwest::StatusCode::NOT_FOUND => (ErrorKind::NotFound, false),
+                Some(status) if status.is_client_error() => {
+                    let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status == reqwest::StatusCode::REQUEST_TIMEOUT;
+                    (ErrorKind::Network, retryable)
+                }
+                _ => (ErrorKind::Network, true),
+            };
+        }
+        if cause.is::<url::ParseError>() {
+            return (ErrorKind::InvalidUrl, false);
+        }
+        if cause.is::<serde_json::Error>() || cause.is::<rss::Error>() {
+            return (ErrorKind::Parse, false);
+        }
+        if cause.is::<std::io::Error>() {
+            return (ErrorKind::Io, false);
+        }
+    }
+    (ErrorKind::Unknown, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn keeps_kind_of_wrapped_app_error() {
+        let error = anyhow::Error::new(AppError::invalid_url("Invalid publication URL."))
+            .context("Failed loading publication");
+        let converted = AppError::from(error);
+        assert_eq!(converted.kind, ErrorKind::InvalidUrl);
+        assert!(!converted.retryable);
+        assert_eq!(converted.message, "Failed loading publication: Invalid publication URL.");
+    }
+
+    #[test]
+    fn classifies_io_errors() {
+        let error = Err::<(), _>(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            .context("Failed writing TXT file.")
+            .unwrap_err();
+        assert_eq!(AppError::from(error).kind, ErrorKind::Io);
+    }
+}
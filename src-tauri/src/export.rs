@@ -1,23 +1,149 @@
+use crate::cache::{HtmlCache, DEFAULT_CACHE_TTL_HOURS};
+use crate::cover::{generate_cover, parse_hex_color, DEFAULT_COVER_BACKGROUND};
+use crate::error::{AppError, ErrorKind};
 use crate::models::{
     CoverMode, ExportFailure, ExportFormat, ExportJobRequest, ExportJobResult, ExportMode, Granularity, MetadataField,
-    OrderMode, PostContent, PostSummary, SortDirection,
+    OrderMode, PostContent, PostMetadataRecord, PostSummary, SortDirection,
+};
+use crate::resume::{ExportState, LastExports};
+use crate::substack::{
+    append_comments, build_http_client, fetch_bytes_with_retries, fetch_post_comments, fetch_post_content,
+    label_text_footnotes, post_content_from_feed, post_stub, EmbedHandling, PlainTextLinkStyle, RateLimiter,
+    RetryPolicy, TextOptions, DEFAULT_RETRY_BASE_DELAY_MS,
 };
-use crate::substack::{build_http_client, fetch_bytes_with_retries, fetch_post_content};
 use crate::utils::{
-    decode_data_url, escape_xml, media_type_to_extension, parse_datetime_flexible, sanitize_filename,
+    decode_data_url, escape_xml, is_rtl_language, media_type_to_extension, normalize_language_tag, parse_datetime_flexible,
+    post_slug, sanitize_filename, single_post_url,
 };
-use anyhow::{anyhow, Context, Result};
-use chrono::Utc;
-use image::ImageFormat;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use log::{info, warn};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
 use uuid::Uuid;
 use zip::write::FileOptions;
 use zip::ZipWriter;
 
 const RETRIES_PER_REQUEST: usize = 3;
+const MAX_RETRIES_PER_REQUEST: usize = 10;
+const MAX_RETRY_BASE_DELAY_MS: u64 = 10_000;
+const DEFAULT_FILENAME_TEMPLATE: &str = "{publication} - {title}";
+const DEFAULT_LANGUAGE: &str = "en";
+const MAX_SUBJECTS_PER_BOOK: usize = 40;
+const DEFAULT_COVER_MAX_DIMENSION: u32 = 1600;
+const MIN_COVER_DIMENSION: u32 = 400;
+const COVER_BACKGROUND: Rgba<u8> = Rgba([238, 236, 232, 255]);
+const AVATAR_MAX_DIMENSION: u32 = 160;
+const DUPLICATE_SHINGLE_WORDS: usize = 5;
+const DUPLICATE_SIMILARITY: f64 = 0.95;
+
+/// Hands out output paths for a single job. Names are compared case-insensitively
+/// (Windows and macOS filesystems are) and a repeated name gets ` (2)`, ` (3)`, ...
+/// inserted before the extension, so no file written by the job replaces another.
+/// In a dry run names are still handed out but writers must not touch the disk.
+#[derive(Debug)]
+struct OutputPaths {
+    dir: PathBuf,
+    used: HashSet<String>,
+    dry_run: bool,
+}
+
+impl OutputPaths {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            used: HashSet::new(),
+            dry_run: false,
+        }
+    }
+
+    /// Marks a file kept from an earlier run so new outputs don't replace it.
+    fn reserve(&mut self, path: &Path) {
+        if let Some(name) = path.file_name() {
+            self.used.insert(name.to_string_lossy().to_lowercase());
+        }
+    }
+
+    fn allocate(&mut self, stem: &str, extension: &str) -> PathBuf {
+        let mut filename = format!("{stem}.{extension}");
+        let mut counter = 2;
+        while !self.used.insert(filename.to_lowercase()) {
+            filename = format!("{stem} ({counter}).{extension}");
+            counter += 1;
+        }
+        self.dir.join(filename)
+    }
+}
+
+/// Job-wide settings shared by every EPUB written in one export.
+#[derive(Debug, Clone, Copy)]
+struct EpubSettings<'a> {
+    metadata_fields: &'a HashSet<MetadataField>,
+    cover: Option<&'a CoverAsset>,
+    custom_css: Option<&'a str>,
+    language: &'a str,
+    publication_title: &'a str,
+    publication_url: &'a str,
+    include_title_page: bool,
+    subtitle_heading: bool,
+    rtl: bool,
+    /// Heading level of chapter titles; body headings are pushed down to stay below it.
+    title_heading_level: u8,
+    /// Downloaded byline avatars keyed by `PostContent::author_avatar_url`.
+    avatars: &'a HashMap<String, CoverAsset>,
+    /// Publication logo shown above the title on the title page.
+    logo: Option<&'a CoverAsset>,
+    /// Conservative markup that Kindle converters handle cleanly.
+    kindle: bool,
+    /// Gather every chapter's footnotes into one "Notes" chapter at the end.
+    endnotes: bool,
+    grouping: ChapterGrouping,
+    /// Append `DARK_CHAPTER_CSS` to the chapter stylesheet.
+    dark_mode: bool,
+}
+
+/// Key that groups combined EPUB chapters under divider pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChapterGrouping {
+    None,
+    Year,
+    Month,
+    Section,
+}
+
+/// Title and author a combined EPUB is published under: the publication's own
+/// unless the export overrides them. Per-post EPUBs use the post's instead.
+#[derive(Debug, Clone, Copy)]
+struct BookIdentity<'a> {
+    title: &'a str,
+    author: &'a str,
+}
+
+/// Upper bounds for a single combined EPUB; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+struct VolumeLimits {
+    max_posts: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct TocHeading {
+    level: u8,
+    id: String,
+    title: String,
+}
 
 #[derive(Debug, Clone)]
 struct CoverAsset {
@@ -26,53 +152,292 @@ struct CoverAsset {
     extension: String,
 }
 
-pub async fn run_export_job(request: ExportJobRequest) -> Result<ExportJobResult> {
+/// Runs the export. Setting `cancel` stops downloading further posts; whatever was
+/// downloaded by then is still written and reported, with a cancellation warning.
+/// `state_dir` is the app data directory holding the last export dates that
+/// incremental exports start from.
+pub async fn run_export_job(
+    mut request: ExportJobRequest,
+    cancel: &AtomicBool,
+    state_dir: Option<&Path>,
+) -> Result<ExportJobResult> {
+    let started = Instant::now();
+    request.apply_legacy_options();
     if request.formats.is_empty() {
-        return Err(anyhow!("At least one format must be selected."));
+        return Err(AppError::invalid_input("At least one format must be selected.").into());
     }
     let output_dir = PathBuf::from(request.output_dir.trim());
     if output_dir.as_os_str().is_empty() {
-        return Err(anyhow!("Output directory is required."));
+        return Err(AppError::invalid_input("Output directory is required.").into());
+    }
+    if !request.output.dry_run {
+        ensure_output_dir_writable(&output_dir)?;
     }
-    fs::create_dir_all(&output_dir).context("Failed to create output directory.")?;
 
-    let selected = select_posts(&request)?;
+    let mut warnings = Vec::new();
+    let mut selected = select_posts(&request, &mut warnings)?;
+    let last_exports = match state_dir {
+        Some(state_dir) if request.incremental => Some(LastExports::load(state_dir)),
+        _ => {
+            if request.incremental {
+                warnings.push("No app data directory for incremental exports; every selected post is exported.".into());
+            }
+            None
+        }
+    };
+    let since = last_exports
+        .as_ref()
+        .and_then(|last_exports| last_exports.newest_post_date(&request.publication_url))
+        .and_then(parse_datetime_flexible);
+    if let Some(since) = since {
+        selected = filter_newer_than(selected, since);
+        if selected.is_empty() {
+            warnings.push(format!(
+                "No new posts since the last export (newest post from {}); nothing was written.",
+                since.format("%Y-%m-%d")
+            ));
+            return Ok(ExportJobResult {
+                succeeded: Vec::new(),
+                failed: Vec::new(),
+                output_files: Vec::new(),
+                warnings,
+                total_words: 0,
+                total_files_bytes: 0,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+    }
     if selected.is_empty() {
-        return Err(anyhow!("No posts matched the current selection."));
+        return Err(AppError::invalid_input("No posts matched the current selection.").into());
     }
-    let ordered = order_posts(selected, &request.order_mode, &request.manual_order, &request.sort_direction);
+    let newest_selected = selected
+        .iter()
+        .filter_map(|post| parse_datetime_flexible(&post.published_at))
+        .max();
+    if matches!(request.order_mode, OrderMode::Manual) && !request.manual_order.is_empty() {
+        check_manual_order(&selected, &request.manual_order, &mut warnings);
+    }
+    // A reading list is exported in the order its links were given.
+    let ordered = if request.post_urls.is_empty() {
+        order_posts(selected, &request.order_mode, &request.manual_order, &request.sort_direction)
+    } else {
+        selected
+    };
 
-    let client = build_http_client()?;
+    let rate_limiter = resolve_rate_limiter(&request, &mut warnings);
+    let rate_limited = AtomicUsize::new(0);
+    let retry = resolve_retry_policy(&request, rate_limiter.as_ref(), &rate_limited, cancel, &mut warnings);
+    let client = build_http_client(
+        request.substack_session_cookie.as_deref(),
+        request.proxy_url.as_deref(),
+        request.request_timeout_secs,
+    )?;
+    let cache = open_html_cache(&request, &mut warnings);
+    let text_options = TextOptions {
+        link_style: resolve_link_style(request.output.plaintext_link_style.as_deref(), &mut warnings),
+        embed_handling: resolve_embed_handling(request.output.embed_handling.as_deref(), &mut warnings),
+        straight_quotes: request.output.straight_quotes,
+        keep_raw_html: request.formats.contains(&ExportFormat::RawHtml),
+        footnote_context: request.output.txt_footnote_context,
+    };
     let mut succeeded = Vec::new();
     let mut failed = Vec::new();
-    let mut warnings = Vec::new();
     let mut contents = Vec::new();
+    let mut output_files = Vec::new();
+    let mut output_paths = OutputPaths::new(output_dir.clone());
+    output_paths.dry_run = request.output.dry_run;
+
+    let mut state = if request.resume {
+        ExportState::load(&output_dir)
+    } else {
+        ExportState::default()
+    };
+    let per_post = matches!(request.granularity, Granularity::PerPost);
+    let extensions = request.formats.iter().map(format_extension).collect::<Vec<_>>();
+    let mut skipped_titles = Vec::new();
+    let total_posts = ordered.len();
+    let mut cancelled = false;
 
-    for summary in ordered {
-        match fetch_post_content(&client, &summary, RETRIES_PER_REQUEST).await {
-            Ok(content) => {
+    for (position, summary) in ordered.into_iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            cancelled = true;
+            break;
+        }
+        if request.resume {
+            if let Some(files) = state.completed_outputs(&summary.id, &extensions).filter(|_| per_post) {
+                for file in files {
+                    output_paths.reserve(Path::new(file));
+                }
+                output_files.extend(files.iter().cloned());
+                skipped_titles.push(summary.title.clone());
+                succeeded.push(summary.id);
+                continue;
+            }
+            if let Some(content) = state.cached_content(&summary.id) {
+                succeeded.push(summary.id);
+                contents.push(content.clone());
+                continue;
+            }
+        }
+        info!("Post {}/{total_posts}: {}", position + 1, summary.title);
+        let from_feed = (request.use_feed_content && !request.epub.include_byline_image)
+            .then(|| post_content_from_feed(&summary, text_options, request.publication_language.as_deref()))
+            .flatten();
+        let fetched = match from_feed {
+            Some(content) => Ok(content),
+            None => fetch_post_content(&client, &summary, retry, cache.as_ref(), text_options).await,
+        };
+        match fetched {
+            Ok(mut content) => {
+                if request.include_comments {
+                    match fetch_post_comments(&client, &content, retry).await {
+                        Ok(comments) => append_comments(&mut content, &comments),
+                        Err(error) => warnings.push(format!(
+                            "Comments for \"{}\" could not be fetched: {error:#}",
+                            content.summary.title
+                        )),
+                    }
+                }
                 succeeded.push(content.summary.id.clone());
+                if request.resume {
+                    state.record_content(&content);
+                    // A strict export that stops on a failure must leave nothing behind.
+                    if !request.output.dry_run && !request.strict_mode {
+                        if let Err(error) = state.save(&output_dir) {
+                            warnings.push(format!("Export progress could not be saved for resuming: {error}"));
+                        }
+                    }
+                }
                 contents.push(content);
             }
+            Err(_) if cancel.load(Ordering::SeqCst) => {
+                cancelled = true;
+                break;
+            }
+            Err(error) if request.strict_mode => {
+                return Err(error.context(format!(
+                    "Post \"{}\" ({}) failed; strict mode stopped the export.",
+                    summary.title, summary.url
+                )));
+            }
             Err(error) => {
+                warn!("Post \"{}\" failed: {error:#}", summary.title);
                 failed.push(ExportFailure {
                     post_id: summary.id,
-                    reason: error.to_string(),
+                    reason: format!("{error:#}"),
                 });
             }
         }
     }
+    if cancelled {
+        warnings.push(format!(
+            "Export cancelled after {} of {total_posts} post(s); only those were exported.",
+            succeeded.len() + failed.len()
+        ));
+    }
+
+    if let Some(query) = request.search_query.as_deref().map(str::trim).filter(|query| !query.is_empty()) {
+        let query = query.to_lowercase();
+        let dropped = drop_contents(&mut contents, &mut succeeded, |content| {
+            !content.plain_text.to_lowercase().contains(&query)
+        });
+        if !dropped.is_empty() {
+            warnings.push(format!(
+                "Left out {} post(s) whose text does not mention \"{query}\": {}",
+                dropped.len(),
+                dropped.join(", ")
+            ));
+            if contents.is_empty() && skipped_titles.is_empty() {
+                return Err(AppError::invalid_input(format!("No downloaded post mentions \"{query}\".")).into());
+            }
+        }
+    }
+    if request.dedupe_content {
+        let duplicates = find_duplicate_contents(&contents);
+        if !duplicates.is_empty() {
+            let originals = duplicates.into_iter().collect::<HashMap<_, _>>();
+            let notes = contents
+                .iter()
+                .filter_map(|content| {
+                    let original = originals.get(&content.summary.id)?;
+                    Some(format!("\"{}\" (same as \"{original}\")", content.summary.title))
+                })
+                .collect::<Vec<_>>();
+            drop_contents(&mut contents, &mut succeeded, |content| originals.contains_key(&content.summary.id));
+            warnings.push(format!(
+                "Left out {} post(s) that repeat an earlier post: {}",
+                notes.len(),
+                notes.join(", ")
+            ));
+        }
+    }
+    if let Some(min_words) = request.min_word_count.filter(|min_words| *min_words > 0) {
+        let dropped = drop_contents(&mut contents, &mut succeeded, |content| content.word_count < min_words);
+        if !dropped.is_empty() {
+            warnings.push(format!(
+                "Left out {} post(s) shorter than {min_words} words: {}",
+                dropped.len(),
+                dropped.join(", ")
+            ));
+        }
+    }
+
+    let throttled = rate_limited.load(Ordering::Relaxed);
+    if throttled > 0 {
+        warnings.push(format!(
+            "Substack rate-limited {throttled} request(s) (HTTP 429); the export waited before retrying. \
+             Lowering the request rate can avoid this."
+        ));
+    }
 
+    let truncated_titles = contents
+        .iter()
+        .filter(|content| content.truncated)
+        .map(|content| format!("\"{}\"", content.summary.title))
+        .collect::<Vec<_>>();
+    if !truncated_titles.is_empty() {
+        warnings.push(paywall_warning(&truncated_titles, request.substack_session_cookie.is_some()));
+    }
+    let undated_titles = contents
+        .iter()
+        .filter(|content| parse_datetime_flexible(&content.summary.published_at).is_none())
+        .map(|content| format!("\"{}\"", content.summary.title))
+        .collect::<Vec<_>>();
+    if !undated_titles.is_empty() {
+        warnings.push(format!(
+            "{} post(s) have no readable publish date; they sort as the oldest and show no publish date: {}",
+            undated_titles.len(),
+            undated_titles.join(", ")
+        ));
+    }
+    if !skipped_titles.is_empty() {
+        warnings.push(format!(
+            "Resumed export skipped {} already exported post(s): {}",
+            skipped_titles.len(),
+            skipped_titles.join(", ")
+        ));
+    }
     if contents.is_empty() {
-        return Err(anyhow!("All post downloads failed; no output generated."));
+        if cancelled || !skipped_titles.is_empty() {
+            return Ok(ExportJobResult {
+                succeeded,
+                failed,
+                total_words: 0,
+                total_files_bytes: total_file_bytes(&output_files),
+                output_files,
+                warnings,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+        return Err(AppError::new(ErrorKind::Network, "All post downloads failed; no output generated.").into());
     }
 
     let metadata_fields: HashSet<MetadataField> = request.metadata_fields.iter().cloned().collect();
     let cover_asset = if request.formats.contains(&ExportFormat::Epub) {
-        match resolve_cover(&request, &client).await {
+        match resolve_cover(&request, &client, retry, &mut warnings).await {
             Ok(cover) => cover,
             Err(error) => {
-                warnings.push(format!("Cover setup issue: {error}"));
+                warnings.push(format!("Cover setup issue: {error:#}"));
                 None
             }
         }
@@ -80,53 +445,516 @@ pub async fn run_export_job(request: ExportJobRequest) -> Result<ExportJobResult
         None
     };
 
-    let mut output_files = Vec::new();
+    let avatars = if request.epub.include_byline_image
+        && request.formats.contains(&ExportFormat::Epub)
+        && !request.output.dry_run
+    {
+        fetch_avatars(&contents, &client, retry, &mut warnings).await
+    } else {
+        HashMap::new()
+    };
+    let wants_logo =
+        request.epub.include_logo && request.formats.contains(&ExportFormat::Epub) && !request.output.dry_run;
+    // A logo that is missing or unreadable is simply left off the title page.
+    let logo = match request.publication_logo_url.as_deref().filter(|_| wants_logo) {
+        Some(url) => fetch_bytes_with_retries(&client, url, retry)
+            .await
+            .ok()
+            .and_then(|bytes| normalize_avatar(&bytes)),
+        None => None,
+    };
+
+    let file_stems = build_post_file_stems(
+        request.output.filename_template.as_deref().unwrap_or(DEFAULT_FILENAME_TEMPLATE),
+        &request.publication_title,
+        &contents,
+    );
+
     if request.formats.contains(&ExportFormat::Txt) {
-        output_files.extend(write_txt_outputs(
-            &output_dir,
+        let files = write_txt_outputs(
+            &mut output_paths,
             &request.publication_title,
             &contents,
+            &file_stems,
             &metadata_fields,
             &request.granularity,
-        )?);
+            request.output.render_subtitle_heading,
+            request.output.txt_include_toc,
+        )?;
+        record_post_outputs(&mut state, per_post, &contents, &files);
+        output_files.extend(files);
     }
-    if request.formats.contains(&ExportFormat::Epub) {
-        output_files.extend(write_epub_outputs(
-            &output_dir,
+    if request.formats.contains(&ExportFormat::Json) {
+        let files = write_json_outputs(
+            &mut output_paths,
             &request.publication_title,
-            request.publication_author.as_deref().unwrap_or("Unknown author"),
             &contents,
-            &metadata_fields,
+            &file_stems,
             &request.granularity,
-            cover_asset.as_ref(),
-        )?);
+        )?;
+        record_post_outputs(&mut state, per_post, &contents, &files);
+        output_files.extend(files);
+    }
+    if request.formats.contains(&ExportFormat::RawHtml) {
+        let (pages, index) = write_raw_html_outputs(
+            &mut output_paths,
+            &request.publication_title,
+            &contents,
+            &file_stems,
+            &mut warnings,
+        )?;
+        if per_post {
+            for (content, page) in contents.iter().zip(&pages) {
+                if let Some(page) = page {
+                    state.record_output(&content.summary.id, page);
+                }
+            }
+        }
+        output_files.extend(pages.into_iter().flatten());
+        output_files.push(index);
+    }
+    if request.formats.contains(&ExportFormat::Epub) {
+        let language = resolve_language(request.epub.language.as_deref(), &contents);
+        if request.epub.dark_mode_css && request.epub.kindle_compatible {
+            warnings.push("Dark mode styles are left out of Kindle-compatible EPUBs.".into());
+        }
+        let settings = EpubSettings {
+            metadata_fields: &metadata_fields,
+            cover: cover_asset.as_ref(),
+            custom_css: request.epub.custom_css.as_deref().filter(|css| !css.trim().is_empty()),
+            language: &language,
+            publication_title: &request.publication_title,
+            publication_url: &request.publication_url,
+            include_title_page: request
+                .epub
+                .include_title_page
+                .unwrap_or(matches!(request.granularity, Granularity::Combined)),
+            subtitle_heading: request.output.render_subtitle_heading,
+            rtl: resolve_rtl(request.epub.reading_direction.as_deref(), &language, &mut warnings),
+            title_heading_level: match request.granularity {
+                Granularity::Combined => request.epub.combined_heading_level.unwrap_or(1).clamp(1, 5),
+                Granularity::PerPost => 1,
+            },
+            avatars: &avatars,
+            logo: logo.as_ref(),
+            kindle: request.epub.kindle_compatible,
+            endnotes: resolve_endnotes(request.epub.footnote_placement.as_deref(), &mut warnings),
+            grouping: match request.granularity {
+                Granularity::Combined => resolve_group_by(request.epub.group_by.as_deref(), &mut warnings),
+                Granularity::PerPost => ChapterGrouping::None,
+            },
+            dark_mode: request.epub.dark_mode_css && !request.epub.kindle_compatible,
+        };
+        let chapter_posts = order_chapters(&contents, &request, settings.grouping);
+        let publication_author = request.publication_author.as_deref().unwrap_or("Unknown author");
+        let files = write_epub_outputs(
+            &mut output_paths,
+            &request.publication_title,
+            publication_author,
+            {
+                let (title, author) = book_title_and_author(&request);
+                BookIdentity {
+                    title,
+                    author: author.unwrap_or(publication_author),
+                }
+            },
+            &chapter_posts,
+            &file_stems,
+            &request.granularity,
+            &settings,
+            VolumeLimits {
+                max_posts: request.epub.max_posts_per_volume.filter(|value| *value > 0),
+                max_bytes: request.epub.max_bytes_per_volume.filter(|value| *value > 0),
+            },
+            &mut warnings,
+        )?;
+        record_post_outputs(&mut state, per_post, &contents, &files);
+        output_files.extend(files);
+    }
+
+    if request.output.package_as_zip && per_post && !output_files.is_empty() {
+        let archive = package_outputs_as_zip(&mut output_paths, &request.publication_title, &output_files)?;
+        if request.output.delete_zipped_files && !request.output.dry_run {
+            let undeleted = output_files
+                .iter()
+                .filter(|file| fs::remove_file(file).is_err())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !undeleted.is_empty() {
+                warnings.push(format!(
+                    "{} file(s) were added to the ZIP but could not be deleted: {}",
+                    undeleted.len(),
+                    undeleted.join(", ")
+                ));
+            }
+        }
+        output_files = vec![archive];
+    }
+
+    if request.output.dry_run {
+        warnings.push(format!("Dry run: {} file(s) would be written; nothing was saved.", output_files.len()));
+    } else if request.resume {
+        if let Err(error) = state.save(&output_dir) {
+            warnings.push(format!("Export progress could not be saved for resuming: {error}"));
+        }
+    }
+    if let (Some(mut last_exports), Some(state_dir), Some(newest)) = (last_exports, state_dir, newest_selected) {
+        if cancelled || !failed.is_empty() {
+            warnings.push(
+                "The last export date was not updated because some posts were not exported; \
+                 the next incremental export tries them again."
+                    .into(),
+            );
+        } else if !request.output.dry_run {
+            last_exports.record(&request.publication_url, &newest.to_rfc3339());
+            if let Err(error) = last_exports.save(state_dir) {
+                warnings.push(format!("The last export date could not be saved: {error}"));
+            }
+        }
     }
 
+    info!(
+        "Export finished: {} post(s) exported, {} failed, {} file(s) in {} ms",
+        succeeded.len(),
+        failed.len(),
+        output_files.len(),
+        started.elapsed().as_millis()
+    );
     Ok(ExportJobResult {
         succeeded,
         failed,
+        total_words: contents.iter().map(|content| content.word_count).sum(),
+        total_files_bytes: total_file_bytes(&output_files),
         output_files,
         warnings,
+        duration_ms: started.elapsed().as_millis() as u64,
     })
 }
 
-fn select_posts(request: &ExportJobRequest) -> Result<Vec<PostSummary>> {
-    match request.mode {
-        ExportMode::EntireProfile => Ok(request.posts.clone()),
+/// Combined size of the files on disk; files that do not exist (dry runs) count as zero.
+fn total_file_bytes(files: &[String]) -> u64 {
+    files
+        .iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Creates the output directory and proves it accepts writes, so a read-only or
+/// full disk fails the job before any post is downloaded.
+fn ensure_output_dir_writable(output_dir: &Path) -> Result<()> {
+    fs::create_dir_all(output_dir).context("Failed to create output directory.")?;
+    let probe = output_dir.join(format!(".write-test-{}", Uuid::new_v4()));
+    fs::write(&probe, b"ok").with_context(|| format!("Output directory {} is not writable.", output_dir.display()))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+fn format_extension(format: &ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Epub => "epub",
+        ExportFormat::Txt => "txt",
+        ExportFormat::Json => "json",
+        ExportFormat::RawHtml => "html",
+    }
+}
+
+/// Remembers which file belongs to which post so a resumed per-post export can
+/// skip it; combined outputs are always rebuilt from the cached content instead.
+fn record_post_outputs(state: &mut ExportState, per_post: bool, contents: &[PostContent], files: &[String]) {
+    if !per_post {
+        return;
+    }
+    for (content, file) in contents.iter().zip(files) {
+        state.record_output(&content.summary.id, file);
+    }
+}
+
+/// Opens the HTML cache when the job asks for it; an unusable cache directory
+/// only costs a warning, since every post can still be downloaded.
+fn open_html_cache(request: &ExportJobRequest, warnings: &mut Vec<String>) -> Option<HtmlCache> {
+    if !request.use_cache {
+        return None;
+    }
+    let ttl_hours = request.cache_ttl_hours.unwrap_or(DEFAULT_CACHE_TTL_HOURS);
+    match HtmlCache::open(ttl_hours, request.refresh_cache, request.substack_session_cookie.as_deref()) {
+        Ok(cache) => Some(cache),
+        Err(error) => {
+            warnings.push(format!("HTML cache unavailable, fetching posts from the network: {error}"));
+            None
+        }
+    }
+}
+
+/// Applies the job's retry settings, clamping them so a typo cannot turn every
+/// failing request into a multi-minute wait.
+fn resolve_retry_policy<'a>(
+    request: &ExportJobRequest,
+    limiter: Option<&'a RateLimiter>,
+    rate_limited: &'a AtomicUsize,
+    cancel: &'a AtomicBool,
+    warnings: &mut Vec<String>,
+) -> RetryPolicy<'a> {
+    let mut retries = request.retry_count.unwrap_or(RETRIES_PER_REQUEST);
+    if retries > MAX_RETRIES_PER_REQUEST {
+        warnings.push(format!(
+            "Retry count {retries} is above the limit; using {MAX_RETRIES_PER_REQUEST}."
+        ));
+        retries = MAX_RETRIES_PER_REQUEST;
+    }
+    let mut base_delay_ms = request.retry_base_delay_ms.unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS);
+    if base_delay_ms > MAX_RETRY_BASE_DELAY_MS {
+        warnings.push(format!(
+            "Retry delay {base_delay_ms} ms is above the limit; using {MAX_RETRY_BASE_DELAY_MS} ms."
+        ));
+        base_delay_ms = MAX_RETRY_BASE_DELAY_MS;
+    }
+    RetryPolicy {
+        retries,
+        base_delay_ms,
+        limiter,
+        rate_limited: Some(rate_limited),
+        cancel: Some(cancel),
+    }
+}
+
+/// Builds the job-wide limiter shared by post, comment and image downloads;
+/// no limit is applied when the request leaves the rate unset.
+fn resolve_rate_limiter(request: &ExportJobRequest, warnings: &mut Vec<String>) -> Option<RateLimiter> {
+    let rate = request.max_requests_per_second?;
+    let limiter = RateLimiter::per_second(rate);
+    if limiter.is_none() {
+        warnings.push(format!("Ignoring request rate limit {rate}; it must be at least one request an hour."));
+    }
+    limiter
+}
+
+fn paywall_warning(titles: &[String], has_cookie: bool) -> String {
+    let listed = titles.join(", ");
+    if has_cookie {
+        format!(
+            "{} post(s) were cut off at the paywall even with a session cookie; it may have been rejected or expired: {listed}",
+            titles.len()
+        )
+    } else {
+        format!(
+            "{} subscriber-only post(s) were truncated to the free preview. Provide a session cookie to export them in full: {listed}",
+            titles.len()
+        )
+    }
+}
+
+fn select_posts(request: &ExportJobRequest, warnings: &mut Vec<String>) -> Result<Vec<PostSummary>> {
+    if !request.post_urls.is_empty() {
+        return Ok(reading_list_posts(&request.post_urls, warnings));
+    }
+    let ranges = request.selection_ranges.as_deref().map(str::trim).filter(|spec| !spec.is_empty());
+    let selected = match request.mode {
+        ExportMode::EntireProfile => request.posts.clone(),
+        ExportMode::SpecificPosts if ranges.is_some() => {
+            if !request.selected_post_ids.is_empty() {
+                return Err(
+                    AppError::invalid_input("Choose posts either by checkbox or by index ranges, not both.").into(),
+                );
+            }
+            parse_selection_ranges(ranges.unwrap_or_default(), request.posts.len())?
+                .into_iter()
+                .map(|index| request.posts[index].clone())
+                .collect()
+        }
         ExportMode::SpecificPosts => {
             if request.selected_post_ids.is_empty() {
-                return Err(anyhow!("No specific posts selected."));
+                return Err(AppError::invalid_input("No specific posts selected.").into());
             }
             let selected_ids: HashSet<&String> = request.selected_post_ids.iter().collect();
-            let selected: Vec<PostSummary> = request
+            request
                 .posts
                 .iter()
                 .filter(|post| selected_ids.contains(&post.id))
                 .cloned()
-                .collect();
-            Ok(selected)
+                .collect()
+        }
+    };
+    let selected = filter_by_title(selected, request.title_contains.as_deref());
+    let selected = filter_by_tags(selected, &request.include_tags, &request.exclude_tags, warnings);
+    filter_by_date_range(
+        selected,
+        request.published_after.as_deref(),
+        request.published_before.as_deref(),
+        warnings,
+    )
+}
+
+/// Stubs for the posts of a reading list, skipping links that are not posts and
+/// links already listed.
+fn reading_list_posts(post_urls: &[String], warnings: &mut Vec<String>) -> Vec<PostSummary> {
+    let mut seen = HashSet::new();
+    let mut invalid = Vec::new();
+    let mut posts = Vec::new();
+    for url in post_urls.iter().map(|url| url.trim()).filter(|url| !url.is_empty()) {
+        match single_post_url(url) {
+            Some(post_url) if seen.insert(post_url.clone()) => posts.push(post_stub(post_url)),
+            Some(_) => {}
+            None => invalid.push(url),
+        }
+    }
+    if !invalid.is_empty() {
+        warnings.push(format!(
+            "Skipped {} link(s) that are not Substack posts: {}",
+            invalid.len(),
+            invalid.join(", ")
+        ));
+    }
+    posts
+}
+
+/// Turns a spec like `1-10,15,20-25` into 0-based indices into a list of `len`
+/// posts, in ascending order and without duplicates.
+fn parse_selection_ranges(spec: &str, len: usize) -> Result<Vec<usize>> {
+    let invalid = |token: &str, reason: &str| -> anyhow::Error {
+        AppError::invalid_input(format!("Invalid post range \"{token}\": {reason}")).into()
+    };
+    let parse_position = |token: &str, value: &str| -> Result<usize> {
+        let position = value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| invalid(token, "expected a number or a range like 3-7."))?;
+        if position == 0 || position > len {
+            return Err(invalid(token, &format!("positions run from 1 to {len}.")));
+        }
+        Ok(position - 1)
+    };
+
+    let mut indices = std::collections::BTreeSet::new();
+    for token in spec.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+        match token.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_position(token, start)?, parse_position(token, end)?);
+                if start > end {
+                    return Err(invalid(token, "the range start is after its end."));
+                }
+                indices.extend(start..=end);
+            }
+            None => {
+                indices.insert(parse_position(token, token)?);
+            }
+        }
+    }
+    if indices.is_empty() {
+        return Err(AppError::invalid_input("The post range selects no posts.").into());
+    }
+    Ok(indices.into_iter().collect())
+}
+
+/// Keeps posts whose title or subtitle contains `needle`, ignoring case.
+fn filter_by_title(posts: Vec<PostSummary>, needle: Option<&str>) -> Vec<PostSummary> {
+    let Some(needle) = needle.map(str::trim).filter(|needle| !needle.is_empty()) else {
+        return posts;
+    };
+    let needle = needle.to_lowercase();
+    posts
+        .into_iter()
+        .filter(|post| {
+            post.title.to_lowercase().contains(&needle)
+                || post.subtitle.as_deref().is_some_and(|subtitle| subtitle.to_lowercase().contains(&needle))
+        })
+        .collect()
+}
+
+/// Keeps posts carrying at least one included tag (when any are given) and none of
+/// the excluded ones, ignoring case. Posts without tag data fail an include filter.
+fn filter_by_tags(
+    posts: Vec<PostSummary>,
+    include_tags: &[String],
+    exclude_tags: &[String],
+    warnings: &mut Vec<String>,
+) -> Vec<PostSummary> {
+    let normalize = |tags: &[String]| -> HashSet<String> {
+        tags.iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    };
+    let include = normalize(include_tags);
+    let exclude = normalize(exclude_tags);
+    if include.is_empty() && exclude.is_empty() {
+        return posts;
+    }
+
+    let mut untagged = 0;
+    let kept = posts
+        .into_iter()
+        .filter(|post| {
+            let Some(tags) = post.tags.as_deref() else {
+                untagged += usize::from(!include.is_empty());
+                return include.is_empty();
+            };
+            let tags = normalize(tags);
+            (include.is_empty() || !tags.is_disjoint(&include)) && tags.is_disjoint(&exclude)
+        })
+        .collect();
+    if untagged > 0 {
+        warnings.push(format!(
+            "Skipped {untagged} post(s) with no tag information; the tag filter could not be applied to them."
+        ));
+    }
+    kept
+}
+
+fn filter_by_date_range(
+    posts: Vec<PostSummary>,
+    published_after: Option<&str>,
+    published_before: Option<&str>,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<PostSummary>> {
+    let parse_bound = |value: Option<&str>, label: &str| -> Result<Option<DateTime<Utc>>> {
+        match value.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(raw) => parse_datetime_flexible(raw)
+                .map(Some)
+                .ok_or_else(|| AppError::invalid_input(format!("Invalid {label} date: {raw}")).into()),
+            None => Ok(None),
+        }
+    };
+    let after = parse_bound(published_after, "published-after")?;
+    let before = parse_bound(published_before, "published-before")?;
+    if after.is_none() && before.is_none() {
+        return Ok(posts);
+    }
+
+    let mut kept = Vec::with_capacity(posts.len());
+    for post in posts {
+        let Some(published) = parse_datetime_flexible(&post.published_at) else {
+            warnings.push(format!(
+                "Skipped \"{}\": publish date \"{}\" could not be parsed for the date filter.",
+                post.title, post.published_at
+            ));
+            continue;
+        };
+        if after.is_some_and(|bound| published < bound) || before.is_some_and(|bound| published > bound) {
+            continue;
         }
+        kept.push(post);
     }
+    Ok(kept)
+}
+
+/// Posts published after `since`. Posts without a readable date are kept, as they
+/// may be new; reading-list links only get their date once the page is fetched.
+fn filter_newer_than(posts: Vec<PostSummary>, since: DateTime<Utc>) -> Vec<PostSummary> {
+    posts
+        .into_iter()
+        .filter(|post| match parse_datetime_flexible(&post.published_at) {
+            Some(published) => published > since,
+            None => true,
+        })
+        .collect()
+}
+
+/// Forgets the last export date of one publication, or of all of them.
+pub fn reset_last_exports(state_dir: &Path, publication_url: Option<&str>) -> Result<()> {
+    let mut last_exports = LastExports::load(state_dir);
+    last_exports.reset(publication_url);
+    last_exports.save(state_dir)
 }
 
 fn order_posts(posts: Vec<PostSummary>, order_mode: &OrderMode, manual_order: &[String], sort_direction: &SortDirection) -> Vec<PostSummary> {
@@ -155,6 +983,107 @@ fn order_posts(posts: Vec<PostSummary>, order_mode: &OrderMode, manual_order: &[
     ordered
 }
 
+/// Warns about ids in the manual order that match no selected post, and about
+/// selected posts the manual order leaves out (`order_posts` puts those last).
+fn check_manual_order(posts: &[PostSummary], manual_order: &[String], warnings: &mut Vec<String>) {
+    let selected_ids = posts.iter().map(|post| post.id.as_str()).collect::<HashSet<_>>();
+    let listed_ids = manual_order.iter().map(String::as_str).collect::<HashSet<_>>();
+
+    let mut reported = HashSet::new();
+    let unknown = manual_order
+        .iter()
+        .map(String::as_str)
+        .filter(|id| !selected_ids.contains(id) && reported.insert(*id))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        warnings.push(format!(
+            "Ignored {} id(s) in the manual order that are not among the selected posts: {}",
+            unknown.len(),
+            unknown.join(", ")
+        ));
+    }
+
+    let missing = posts
+        .iter()
+        .filter(|post| !listed_ids.contains(post.id.as_str()))
+        .map(|post| format!("\"{}\"", post.title))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        warnings.push(format!(
+            "{} selected post(s) are missing from the manual order and were placed at the end by date: {}",
+            missing.len(),
+            missing.join(", ")
+        ));
+    }
+}
+
+/// Reading order for combined EPUB chapters. `chapter_order` re-sorts by date
+/// independently of the selection sort; a manual order is always kept as is.
+/// With a grouping, chapters of the same group are then brought together, groups
+/// ordered by their first chapter and chapters keeping their order inside a group.
+fn order_chapters<'a>(
+    contents: &'a [PostContent],
+    request: &ExportJobRequest,
+    grouping: ChapterGrouping,
+) -> Cow<'a, [PostContent]> {
+    let manual = matches!(request.order_mode, OrderMode::Manual) && !request.manual_order.is_empty();
+    let mut ordered = match &request.epub.chapter_order {
+        Some(direction) if !manual && matches!(request.granularity, Granularity::Combined) => {
+            let mut ordered = contents.to_vec();
+            ordered.sort_by(|a, b| compare_post_dates(&a.summary, &b.summary, direction));
+            Cow::Owned(ordered)
+        }
+        _ => Cow::Borrowed(contents),
+    };
+    if grouping != ChapterGrouping::None {
+        let mut first_seen: HashMap<Option<String>, usize> = HashMap::new();
+        for post in ordered.iter() {
+            let next = first_seen.len();
+            first_seen.entry(group_label(&post.summary, grouping)).or_insert(next);
+        }
+        ordered
+            .to_mut()
+            .sort_by_key(|post| first_seen.get(&group_label(&post.summary, grouping)).copied());
+    }
+    ordered
+}
+
+/// Divider label of the group a post falls in; `None` without a grouping.
+fn group_label(post: &PostSummary, grouping: ChapterGrouping) -> Option<String> {
+    let date_label = |format: &str| {
+        parse_datetime_flexible(&post.published_at)
+            .map(|date| date.format(format).to_string())
+            .unwrap_or_else(|| "Undated".to_string())
+    };
+    match grouping {
+        ChapterGrouping::None => None,
+        ChapterGrouping::Year => Some(date_label("%Y")),
+        ChapterGrouping::Month => Some(date_label("%B %Y")),
+        ChapterGrouping::Section => Some(
+            post.section
+                .as_deref()
+                .map(str::trim)
+                .filter(|section| !section.is_empty())
+                .unwrap_or("Other")
+                .to_string(),
+        ),
+    }
+}
+
+/// Index of the first chapter of each group, with the group's label.
+fn chapter_groups(posts: &[PostContent], grouping: ChapterGrouping) -> Vec<(usize, String)> {
+    let mut groups: Vec<(usize, String)> = Vec::new();
+    for (index, post) in posts.iter().enumerate() {
+        let Some(label) = group_label(&post.summary, grouping) else {
+            continue;
+        };
+        if groups.last().map(|(_, last)| last) != Some(&label) {
+            groups.push((index, label));
+        }
+    }
+    groups
+}
+
 fn compare_post_dates(a: &PostSummary, b: &PostSummary, sort_direction: &SortDirection) -> std::cmp::Ordering {
     let a_ts = parse_datetime_flexible(&a.published_at)
         .map(|value| value.timestamp_millis())
@@ -173,85 +1102,471 @@ fn compare_post_dates(a: &PostSummary, b: &PostSummary, sort_direction: &SortDir
     }
 }
 
-async fn resolve_cover(request: &ExportJobRequest, client: &reqwest::Client) -> Result<Option<CoverAsset>> {
-    match request.cover_mode {
+/// An explicit override wins; otherwise the most common language declared by the
+/// fetched post pages is used, falling back to English when none declare one.
+fn resolve_language(override_language: Option<&str>, posts: &[PostContent]) -> String {
+    if let Some(language) = override_language.and_then(normalize_language_tag) {
+        return language;
+    }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for language in posts.iter().filter_map(|post| post.language.as_deref()) {
+        *counts.entry(language).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(language, _)| language.to_string())
+        .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string())
+}
+
+/// Removes the posts matching `drop` from the job's output and its succeeded list,
+/// returning their quoted titles for a warning.
+fn drop_contents(
+    contents: &mut Vec<PostContent>,
+    succeeded: &mut Vec<String>,
+    drop: impl Fn(&PostContent) -> bool,
+) -> Vec<String> {
+    let mut dropped = Vec::new();
+    contents.retain(|content| {
+        if !drop(content) {
+            return true;
+        }
+        succeeded.retain(|id| id != &content.summary.id);
+        dropped.push(format!("\"{}\"", content.summary.title));
+        false
+    });
+    dropped
+}
+
+/// Finds posts whose text repeats an earlier post: identical once case and spacing
+/// are ignored, or sharing at least 95% of their five-word shingles. The earliest
+/// published copy is kept. Returns `(duplicate id, kept title)` pairs.
+fn find_duplicate_contents(contents: &[PostContent]) -> Vec<(String, String)> {
+    let mut order = (0..contents.len()).collect::<Vec<_>>();
+    // Undated posts sort last so a dated original is the one kept.
+    order.sort_by_key(|&index| {
+        let published = parse_datetime_flexible(&contents[index].summary.published_at);
+        (published.is_none(), published)
+    });
+
+    let mut kept: Vec<(usize, u64, HashSet<u64>)> = Vec::new();
+    let mut duplicates = Vec::new();
+    for index in order {
+        let words = contents[index]
+            .plain_text
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        let fingerprint = hash_value(&words);
+        let shingles = words.windows(DUPLICATE_SHINGLE_WORDS).map(hash_value).collect::<HashSet<_>>();
+        let original = kept.iter().find(|(_, kept_fingerprint, kept_shingles)| {
+            *kept_fingerprint == fingerprint || shingle_similarity(kept_shingles, &shingles) >= DUPLICATE_SIMILARITY
+        });
+        match original {
+            Some((original, _, _)) => duplicates.push((
+                contents[index].summary.id.clone(),
+                contents[*original].summary.title.clone(),
+            )),
+            None => kept.push((index, fingerprint, shingles)),
+        }
+    }
+    duplicates
+}
+
+fn hash_value<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Jaccard similarity of two shingle sets; sets of very different sizes are
+/// rejected before counting the overlap.
+fn shingle_similarity(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let (smaller, larger) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if smaller.is_empty() || (smaller.len() as f64) < larger.len() as f64 * DUPLICATE_SIMILARITY {
+        return 0.0;
+    }
+    let shared = smaller.iter().filter(|shingle| larger.contains(shingle)).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+fn resolve_link_style(value: Option<&str>, warnings: &mut Vec<String>) -> PlainTextLinkStyle {
+    match value.map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("strip") => PlainTextLinkStyle::Strip,
+        Some("inline") => PlainTextLinkStyle::Inline,
+        Some("footnote") => PlainTextLinkStyle::Footnote,
+        Some(other) => {
+            warnings.push(format!("Unknown plain-text link style \"{other}\"; expected \"inline\" or \"footnote\"."));
+            PlainTextLinkStyle::Strip
+        }
+    }
+}
+
+fn resolve_embed_handling(value: Option<&str>, warnings: &mut Vec<String>) -> EmbedHandling {
+    match value.map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("remove") => EmbedHandling::Remove,
+        Some("placeholder") => EmbedHandling::Placeholder,
+        Some("keep") => EmbedHandling::Keep,
+        Some(other) => {
+            warnings.push(format!(
+                "Unknown embed handling \"{other}\"; expected \"remove\", \"placeholder\" or \"keep\"."
+            ));
+            EmbedHandling::Remove
+        }
+    }
+}
+
+fn resolve_group_by(value: Option<&str>, warnings: &mut Vec<String>) -> ChapterGrouping {
+    match value.map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("none") => ChapterGrouping::None,
+        Some("year") => ChapterGrouping::Year,
+        Some("month") => ChapterGrouping::Month,
+        Some("section") => ChapterGrouping::Section,
+        Some(other) => {
+            warnings.push(format!(
+                "Unknown chapter grouping \"{other}\"; expected \"year\", \"month\" or \"section\"."
+            ));
+            ChapterGrouping::None
+        }
+    }
+}
+
+/// `true` for `end_of_book`; chapters keep their own footnotes otherwise.
+fn resolve_endnotes(placement: Option<&str>, warnings: &mut Vec<String>) -> bool {
+    match placement.map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("per_chapter") => false,
+        Some("end_of_book") => true,
+        Some(other) => {
+            warnings.push(format!(
+                "Unknown footnote placement \"{other}\"; expected \"per_chapter\" or \"end_of_book\"."
+            ));
+            false
+        }
+    }
+}
+
+/// Right-to-left when the request says so, otherwise when the book language is
+/// an RTL script; anything unrecognised falls back to left-to-right.
+fn resolve_rtl(reading_direction: Option<&str>, language: &str, warnings: &mut Vec<String>) -> bool {
+    match reading_direction.map(|value| value.trim().to_ascii_lowercase()) {
+        Some(direction) if direction == "rtl" => true,
+        Some(direction) if direction == "ltr" => false,
+        Some(direction) if direction.is_empty() || direction == "auto" => is_rtl_language(language),
+        Some(direction) => {
+            warnings.push(format!("Unknown reading direction \"{direction}\"; expected \"ltr\" or \"rtl\"."));
+            is_rtl_language(language)
+        }
+        None => is_rtl_language(language),
+    }
+}
+
+async fn resolve_cover(
+    request: &ExportJobRequest,
+    client: &reqwest::Client,
+    retry: RetryPolicy<'_>,
+    warnings: &mut Vec<String>,
+) -> Result<Option<CoverAsset>> {
+    let max_dimension = request.epub.cover_max_dimension.unwrap_or(DEFAULT_COVER_MAX_DIMENSION);
+    match request.epub.cover_mode.clone().unwrap_or_default() {
         CoverMode::Custom => {
-            let Some(data_url) = request.custom_cover_data_url.as_deref() else {
-                return Err(anyhow!("Custom cover mode selected but no file uploaded."));
+            let Some(data_url) = request.epub.custom_cover_data_url.as_deref() else {
+                return Err(AppError::invalid_input("Custom cover mode selected but no file uploaded.").into());
             };
             let (bytes, mime_hint) = decode_data_url(data_url)?;
-            Ok(Some(normalize_cover_asset(bytes, Some(mime_hint))?))
+            Ok(Some(normalize_cover_asset(bytes, Some(mime_hint), max_dimension, warnings)?))
         }
         CoverMode::SubstackAuthor => {
             let Some(cover_url) = request.author_cover_url.as_deref() else {
+                warnings.push(
+                    "The publication has no author image, so the EPUB has no cover; choose the generated cover instead."
+                        .into(),
+                );
                 return Ok(None);
             };
-            let bytes = fetch_bytes_with_retries(client, cover_url, RETRIES_PER_REQUEST).await?;
-            Ok(Some(normalize_cover_asset(bytes, None)?))
+            let bytes = fetch_bytes_with_retries(client, cover_url, retry).await?;
+            Ok(Some(normalize_cover_asset(bytes, None, max_dimension, warnings)?))
         }
+        CoverMode::Generated => generated_cover(request).map(Some),
     }
 }
 
-fn normalize_cover_asset(bytes: Vec<u8>, mime_hint: Option<String>) -> Result<CoverAsset> {
-    if bytes.is_empty() {
-        return Err(anyhow!("Cover image bytes are empty."));
-    }
-    let guessed = image::guess_format(&bytes).ok();
-    let (media_type, extension) = if let Some(format) = guessed {
-        match format {
-            ImageFormat::Png => ("image/png".to_string(), "png".to_string()),
-            ImageFormat::Jpeg => ("image/jpeg".to_string(), "jpg".to_string()),
-            ImageFormat::Gif => ("image/gif".to_string(), "gif".to_string()),
-            ImageFormat::WebP => ("image/webp".to_string(), "webp".to_string()),
-            _ => {
-                let mime = mime_hint.unwrap_or_else(|| "image/jpeg".to_string());
-                (mime.clone(), media_type_to_extension(&mime).to_string())
+/// Downloads each distinct byline avatar once. Chapters whose avatar is missing
+/// or unreadable simply go without one.
+async fn fetch_avatars(
+    contents: &[PostContent],
+    client: &reqwest::Client,
+    retry: RetryPolicy<'_>,
+    warnings: &mut Vec<String>,
+) -> HashMap<String, CoverAsset> {
+    let mut avatars = HashMap::new();
+    let mut failed = HashSet::new();
+    for url in contents.iter().filter_map(|content| content.author_avatar_url.as_deref()) {
+        if avatars.contains_key(url) || failed.contains(url) {
+            continue;
+        }
+        let avatar = fetch_bytes_with_retries(client, url, retry)
+            .await
+            .ok()
+            .and_then(|bytes| normalize_avatar(&bytes));
+        match avatar {
+            Some(avatar) => {
+                avatars.insert(url.to_string(), avatar);
+            }
+            None => {
+                failed.insert(url);
             }
         }
-    } else {
-        let mime = mime_hint.unwrap_or_else(|| "image/jpeg".to_string());
-        (mime.clone(), media_type_to_extension(&mime).to_string())
-    };
-
+    }
+    if !failed.is_empty() {
+        warnings.push(format!(
+            "{} author avatar(s) could not be downloaded; those chapters have no byline picture.",
+            failed.len()
+        ));
+    }
+    avatars
+}
+
+/// Decodes a byline photo or publication logo and re-encodes it at chapter-header size.
+fn normalize_avatar(bytes: &[u8]) -> Option<CoverAsset> {
+    let decoded = image::load_from_memory(bytes).ok()?;
+    let resized = if decoded.width().max(decoded.height()) > AVATAR_MAX_DIMENSION {
+        decoded.resize(AVATAR_MAX_DIMENSION, AVATAR_MAX_DIMENSION, FilterType::Lanczos3)
+    } else {
+        decoded
+    };
+    encode_cover(&resized).ok()
+}
+
+/// Title and author of combined EPUBs: the overrides when set, else the
+/// publication's own.
+fn book_title_and_author(request: &ExportJobRequest) -> (&str, Option<&str>) {
+    fn non_empty(value: Option<&str>) -> Option<&str> {
+        value.map(str::trim).filter(|value| !value.is_empty())
+    }
+    (
+        non_empty(request.epub.book_title_override.as_deref()).unwrap_or(&request.publication_title),
+        non_empty(request.epub.book_author_override.as_deref()).or(request.publication_author.as_deref()),
+    )
+}
+
+fn generated_cover(request: &ExportJobRequest) -> Result<CoverAsset> {
+    let background = match request.epub.cover_background.as_deref() {
+        Some(value) => parse_hex_color(value)
+            .ok_or_else(|| AppError::invalid_input(format!("Cover background '{value}' is not a #rrggbb color.")))?,
+        None => DEFAULT_COVER_BACKGROUND,
+    };
+    // The cover names the book the way its OPF metadata does.
+    let (title, author) = match request.granularity {
+        Granularity::Combined => book_title_and_author(request),
+        Granularity::PerPost => (request.publication_title.as_str(), request.publication_author.as_deref()),
+    };
+    let bytes = generate_cover(title, author, background)?;
     Ok(CoverAsset {
         bytes,
-        media_type,
-        extension,
+        media_type: "image/png".to_string(),
+        extension: "png".to_string(),
     })
 }
 
+/// Keeps JPEG and PNG covers as they are and converts WebP and GIF ones to one of
+/// them, since many e-readers show a blank cover otherwise. Formats without a
+/// decoder in this build (AVIF among them) are kept as they are, with a warning.
+/// Covers larger than `max_dimension` are scaled down; tiny ones (author avatars)
+/// are centered on a plain book-shaped canvas instead of being stretched.
+fn normalize_cover_asset(
+    bytes: Vec<u8>,
+    mime_hint: Option<String>,
+    max_dimension: u32,
+    warnings: &mut Vec<String>,
+) -> Result<CoverAsset> {
+    if bytes.is_empty() {
+        return Err(AppError::invalid_input("Cover image bytes are empty.").into());
+    }
+    let Ok(format) = image::guess_format(&bytes) else {
+        let mime = mime_hint.unwrap_or_else(|| "image/jpeg".to_string());
+        let extension = media_type_to_extension(&mime).to_string();
+        return Ok(CoverAsset {
+            bytes,
+            media_type: mime,
+            extension,
+        });
+    };
+    let reader_safe = matches!(format, ImageFormat::Png | ImageFormat::Jpeg);
+    let original = |bytes: Vec<u8>| CoverAsset {
+        bytes,
+        media_type: format.to_mime_type().to_string(),
+        extension: format.extensions_str()[0].to_string(),
+    };
+
+    if !reader_safe && !format.reading_enabled() {
+        warnings.push(format!(
+            "Cover image is {} and cannot be converted; some e-readers may not show it.",
+            format_name(format)
+        ));
+        return Ok(original(bytes));
+    }
+    let decoded = match image::load_from_memory_with_format(&bytes, format) {
+        Ok(decoded) => decoded,
+        Err(error) => {
+            if !reader_safe {
+                warnings.push(format!(
+                    "Cover image is {} and could not be converted ({error}); some e-readers may not show it.",
+                    format_name(format)
+                ));
+            }
+            return Ok(original(bytes));
+        }
+    };
+
+    let max_dimension = max_dimension.max(MIN_COVER_DIMENSION);
+    let (width, height) = (decoded.width(), decoded.height());
+    let reshaped = if width.max(height) > max_dimension {
+        Some(decoded.resize(max_dimension, max_dimension, FilterType::Lanczos3))
+    } else if width.max(height) < MIN_COVER_DIMENSION {
+        Some(pad_small_cover(&decoded, max_dimension))
+    } else {
+        None
+    };
+
+    if reshaped.is_none() && reader_safe {
+        return Ok(original(bytes));
+    }
+    let converted = encode_cover(reshaped.as_ref().unwrap_or(&decoded))?;
+    if !reader_safe {
+        warnings.push(format!(
+            "Cover image was {} and has been converted to {} for e-reader compatibility.",
+            format_name(format),
+            converted.extension.to_uppercase()
+        ));
+    }
+    Ok(converted)
+}
+
+/// Places an undersized image, unscaled, in the middle of a 2:3 canvas.
+fn pad_small_cover(image: &DynamicImage, max_dimension: u32) -> DynamicImage {
+    let canvas_height = max_dimension.min(DEFAULT_COVER_MAX_DIMENSION);
+    let canvas_width = canvas_height * 2 / 3;
+    let mut canvas = RgbaImage::from_pixel(canvas_width, canvas_height, COVER_BACKGROUND);
+    let x = (canvas_width.saturating_sub(image.width()) / 2) as i64;
+    let y = (canvas_height.saturating_sub(image.height()) / 2) as i64;
+    imageops::overlay(&mut canvas, &image.to_rgba8(), x, y);
+    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+}
+
+/// Encodes the cover as PNG when it has an alpha channel, JPEG otherwise.
+fn encode_cover(image: &DynamicImage) -> Result<CoverAsset> {
+    let mut out = std::io::Cursor::new(Vec::new());
+    if image.color().has_alpha() {
+        image.write_to(&mut out, ImageFormat::Png)?;
+        Ok(CoverAsset {
+            bytes: out.into_inner(),
+            media_type: "image/png".to_string(),
+            extension: "png".to_string(),
+        })
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut out, ImageFormat::Jpeg)?;
+        Ok(CoverAsset {
+            bytes: out.into_inner(),
+            media_type: "image/jpeg".to_string(),
+            extension: "jpg".to_string(),
+        })
+    }
+}
+
+fn format_name(format: ImageFormat) -> String {
+    format!("{format:?}").to_uppercase()
+}
+
+/// Expands the filename template for every post. Repeated names are left as-is
+/// here; `OutputPaths` disambiguates them when the files are written.
+fn build_post_file_stems(template: &str, publication_title: &str, posts: &[PostContent]) -> Vec<String> {
+    let template = if template.trim().is_empty() {
+        DEFAULT_FILENAME_TEMPLATE
+    } else {
+        template
+    };
+    posts
+        .iter()
+        .enumerate()
+        .map(|(index, post)| sanitize_filename(&expand_filename_template(template, publication_title, post, index + 1)))
+        .collect()
+}
+
+/// Supported tokens: `{publication}`, `{title}`, `{author}`, `{slug}`, `{index}`
+/// and `{date}` / `{date:<strftime>}`. Unknown tokens are left untouched.
+fn expand_filename_template(template: &str, publication_title: &str, post: &PostContent, index: usize) -> String {
+    let token_regex = Regex::new(r"\{(\w+)(?::([^}]*))?\}").expect("valid filename-template regex");
+    token_regex
+        .replace_all(template, |caps: &regex::Captures| {
+            let original = caps.get(0).map(|m| m.as_str()).unwrap_or_default().to_string();
+            match caps.get(1).map(|m| m.as_str()).unwrap_or_default() {
+                "publication" => publication_title.to_string(),
+                "title" => post.summary.title.clone(),
+                "author" => post.summary.author.clone().unwrap_or_default(),
+                "slug" => post_slug(&post.summary.url, &post.summary.title),
+                "index" => index.to_string(),
+                "date" => {
+                    let format = caps.get(2).map(|m| m.as_str()).unwrap_or("%Y-%m-%d");
+                    parse_datetime_flexible(&post.summary.published_at)
+                        .and_then(|date| {
+                            use std::fmt::Write as _;
+                            let mut out = String::new();
+                            write!(out, "{}", date.format(format)).ok().map(|_| out)
+                        })
+                        .unwrap_or_default()
+                }
+                _ => original,
+            }
+        })
+        .into_owned()
+}
+
+#[allow(clippy::too_many_arguments)]
 fn write_txt_outputs(
-    output_dir: &Path,
+    output_paths: &mut OutputPaths,
     publication_title: &str,
     posts: &[PostContent],
+    file_stems: &[String],
     metadata_fields: &HashSet<MetadataField>,
     granularity: &Granularity,
+    subtitle_heading: bool,
+    include_toc: bool,
 ) -> Result<Vec<String>> {
     match granularity {
         Granularity::PerPost => posts
             .iter()
-            .map(|post| {
-                let filename = format!(
-                    "{} - {}.txt",
-                    sanitize_filename(publication_title),
-                    sanitize_filename(&post.summary.title)
-                );
-                let file_path = output_dir.join(filename);
-                let content = render_txt_post(post, metadata_fields);
-                fs::write(&file_path, content).context("Failed writing TXT file.")?;
+            .zip(file_stems)
+            .map(|(post, stem)| {
+                let file_path = output_paths.allocate(stem, "txt");
+                if !output_paths.dry_run {
+                    let content = render_txt_post(post, metadata_fields, subtitle_heading, false);
+                    fs::write(&file_path, content).context("Failed writing TXT file.")?;
+                }
                 Ok(file_path.to_string_lossy().to_string())
             })
             .collect(),
         Granularity::Combined => {
-            let filename = format!("{} - combined.txt", sanitize_filename(publication_title));
-            let file_path = output_dir.join(filename);
+            let stem = format!("{} - combined", sanitize_filename(publication_title));
+            let file_path = output_paths.allocate(&stem, "txt");
+            if output_paths.dry_run {
+                return Ok(vec![file_path.to_string_lossy().to_string()]);
+            }
             let mut combined = String::new();
             combined.push_str(&format!("Publication: {}\n", publication_title));
             combined.push_str(&format!("Generated: {}\n\n", Utc::now().to_rfc3339()));
+            if include_toc {
+                combined.push_str(&render_txt_toc(posts));
+            }
 
-            for post in posts {
+            for (index, post) in posts.iter().enumerate() {
                 combined.push_str("============================================================\n");
-                combined.push_str(&render_txt_post(post, metadata_fields));
+                if include_toc {
+                    combined.push_str(&format!("## {}\n", txt_toc_label(index, post)));
+                }
+                combined.push_str(&render_txt_post(post, metadata_fields, subtitle_heading, true));
                 combined.push('\n');
             }
             fs::write(&file_path, combined).context("Failed writing combined TXT file.")?;
@@ -260,13 +1575,45 @@ fn write_txt_outputs(
     }
 }
 
-fn render_txt_post(post: &PostContent, metadata_fields: &HashSet<MetadataField>) -> String {
+/// Numbered list of the posts in file order. Each entry matches the `## [n] Title`
+/// marker written before that post, so searching for an entry finds the post.
+fn render_txt_toc(posts: &[PostContent]) -> String {
+    let mut toc = String::from("Contents\n");
+    for (index, post) in posts.iter().enumerate() {
+        toc.push_str(&txt_toc_label(index, post));
+        toc.push('\n');
+    }
+    toc.push('\n');
+    toc
+}
+
+fn txt_toc_label(index: usize, post: &PostContent) -> String {
+    format!("[{}] {}", index + 1, post.summary.title.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// `label_footnotes` names the post above its footnote list, for combined files.
+fn render_txt_post(
+    post: &PostContent,
+    metadata_fields: &HashSet<MetadataField>,
+    subtitle_heading: bool,
+    label_footnotes: bool,
+) -> String {
     let mut out = String::new();
     out.push_str(&format!("{}\n", post.summary.title));
     out.push_str("------------------------------------------------------------\n");
+    if let Some(subtitle) = post_subtitle(post).filter(|_| subtitle_heading) {
+        out.push_str(&format!("{subtitle}\n\n"));
+    }
     out.push_str(&render_metadata_lines(post, metadata_fields));
     out.push('\n');
-    out.push_str(post.plain_text.trim());
+    if let Some(audio_url) = post.summary.audio_url.as_deref() {
+        out.push_str(&format!("Listen: {audio_url}\n\n"));
+    }
+    if label_footnotes {
+        out.push_str(label_text_footnotes(&post.plain_text, &post.summary.title).trim());
+    } else {
+        out.push_str(post.plain_text.trim());
+    }
     out.push('\n');
     out
 }
@@ -282,12 +1629,21 @@ fn render_metadata_lines(post: &PostContent, metadata_fields: &HashSet<MetadataF
             post.summary.author.as_deref().unwrap_or("Unknown")
         ));
     }
-    if metadata_fields.contains(&MetadataField::PublishedAt) {
+    if metadata_fields.contains(&MetadataField::PublishedAt) && !post.summary.published_at.trim().is_empty() {
         fields.push(format!("Published: {}", post.summary.published_at));
     }
     if metadata_fields.contains(&MetadataField::Url) {
         fields.push(format!("URL: {}", post.summary.url));
     }
+    if metadata_fields.contains(&MetadataField::CanonicalUrl) {
+        fields.push(format!("Canonical URL: {}", canonical_url(&post.summary)));
+    }
+    if metadata_fields.contains(&MetadataField::Slug) {
+        fields.push(format!("Slug: {}", post_slug(&post.summary.url, &post.summary.title)));
+    }
+    if metadata_fields.contains(&MetadataField::Section) {
+        fields.push(format!("Section: {}", post.summary.section.as_deref().unwrap_or("N/A")));
+    }
     if metadata_fields.contains(&MetadataField::Tags) {
         let tags = post
             .summary
@@ -311,6 +1667,15 @@ fn render_metadata_lines(post: &PostContent, metadata_fields: &HashSet<MetadataF
                 .unwrap_or_else(|| "N/A".to_string())
         ));
     }
+    if metadata_fields.contains(&MetadataField::WordCount) {
+        fields.push(format!("Word count: {}", post.word_count));
+    }
+    if metadata_fields.contains(&MetadataField::Likes) {
+        fields.push(format!("Likes: {}", format_count(post.summary.reaction_count)));
+    }
+    if metadata_fields.contains(&MetadataField::CommentCount) {
+        fields.push(format!("Comments: {}", format_count(post.summary.comment_count)));
+    }
     if metadata_fields.contains(&MetadataField::Summary) {
         fields.push(format!(
             "Summary: {}",
@@ -320,50 +1685,249 @@ fn render_metadata_lines(post: &PostContent, metadata_fields: &HashSet<MetadataF
     fields.join("\n")
 }
 
+fn format_count(count: Option<u64>) -> String {
+    count.map(|value| value.to_string()).unwrap_or_else(|| "N/A".to_string())
+}
+
+/// The page's declared canonical URL, or the fetched URL when it declared none.
+fn canonical_url(summary: &PostSummary) -> &str {
+    summary.canonical_url.as_deref().unwrap_or(&summary.url)
+}
+
+fn write_json_outputs(
+    output_paths: &mut OutputPaths,
+    publication_title: &str,
+    posts: &[PostContent],
+    file_stems: &[String],
+    granularity: &Granularity,
+) -> Result<Vec<String>> {
+    match granularity {
+        Granularity::PerPost => posts
+            .iter()
+            .zip(file_stems)
+            .map(|(post, stem)| {
+                let file_path = output_paths.allocate(stem, "json");
+                if !output_paths.dry_run {
+                    let content = serde_json::to_string_pretty(&build_metadata_record(post))?;
+                    fs::write(&file_path, content).context("Failed writing JSON file.")?;
+                }
+                Ok(file_path.to_string_lossy().to_string())
+            })
+            .collect(),
+        Granularity::Combined => {
+            let stem = format!("{} - combined", sanitize_filename(publication_title));
+            let file_path = output_paths.allocate(&stem, "json");
+            if output_paths.dry_run {
+                return Ok(vec![file_path.to_string_lossy().to_string()]);
+            }
+            let records = posts.iter().map(build_metadata_record).collect::<Vec<_>>();
+            let content = serde_json::to_string_pretty(&records)?;
+            fs::write(&file_path, content).context("Failed writing combined JSON file.")?;
+            Ok(vec![file_path.to_string_lossy().to_string()])
+        }
+    }
+}
+
+/// Bundles the per-post files of one export into `{publication}.zip` next to them,
+/// each stored under its file name.
+fn package_outputs_as_zip(output_paths: &mut OutputPaths, publication_title: &str, files: &[String]) -> Result<String> {
+    let zip_path = output_paths.allocate(&sanitize_filename(publication_title), "zip");
+    if output_paths.dry_run {
+        return Ok(zip_path.to_string_lossy().to_string());
+    }
+    let mut zip = ZipWriter::new(File::create(&zip_path).context("Failed to create ZIP archive.")?);
+    let deflated = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for file in files {
+        let path = Path::new(file);
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| file.clone());
+        zip.start_file(name, deflated)?;
+        zip.write_all(&fs::read(path).with_context(|| format!("Failed reading {file} for the ZIP archive."))?)?;
+    }
+    zip.finish()?;
+    Ok(zip_path.to_string_lossy().to_string())
+}
+
+/// Writes every post page exactly as it was downloaded, one file per post whatever
+/// the granularity, plus an index page linking them. Returns the page written for
+/// each post (`None` when the post has no page, e.g. it came from the feed) and
+/// the index path.
+fn write_raw_html_outputs(
+    output_paths: &mut OutputPaths,
+    publication_title: &str,
+    posts: &[PostContent],
+    file_stems: &[String],
+    warnings: &mut Vec<String>,
+) -> Result<(Vec<Option<String>>, String)> {
+    let mut pages = Vec::new();
+    let mut index_entries = Vec::new();
+    let mut missing = Vec::new();
+    for (post, stem) in posts.iter().zip(file_stems) {
+        let Some(raw_html) = post.raw_html.as_deref() else {
+            missing.push(format!("\"{}\"", post.summary.title));
+            pages.push(None);
+            continue;
+        };
+        let file_path = output_paths.allocate(stem, "html");
+        if !output_paths.dry_run {
+            fs::write(&file_path, raw_html).context("Failed writing raw HTML file.")?;
+        }
+        let file_name = file_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        index_entries.push(format!(
+            r#"<li><a href="{}">{}</a> (<a href="{}">original</a>)</li>"#,
+            escape_xml(&encode_path_segment(&file_name)),
+            escape_xml(&post.summary.title),
+            escape_xml(&post.summary.url)
+        ));
+        pages.push(Some(file_path.to_string_lossy().to_string()));
+    }
+    if !missing.is_empty() {
+        warnings.push(format!(
+            "No original HTML for {} post(s) that were not downloaded from their page in this run: {}",
+            missing.len(),
+            missing.join(", ")
+        ));
+    }
+
+    let index_path = output_paths.allocate(&format!("{} - index", sanitize_filename(publication_title)), "html");
+    if !output_paths.dry_run {
+        let index = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8"/>
+  <title>{title}</title>
+</head>
+<body>
+  <h1>{title}</h1>
+  <ol>
+    {}
+  </ol>
+</body>
+</html>
+"#,
+            index_entries.join("\n    "),
+            title = escape_xml(publication_title)
+        );
+        fs::write(&index_path, index).context("Failed writing raw HTML index.")?;
+    }
+    Ok((pages, index_path.to_string_lossy().to_string()))
+}
+
+/// Percent-encodes a file name for use as a relative link.
+fn encode_path_segment(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn build_metadata_record(post: &PostContent) -> PostMetadataRecord {
+    PostMetadataRecord {
+        // The record's own reading time (resolved from the page) replaces the summary's.
+        summary: PostSummary {
+            reading_time_minutes: None,
+            ..post.summary.clone()
+        },
+        slug: post_slug(&post.summary.url, &post.summary.title),
+        reading_time_minutes: post.reading_time_minutes,
+        word_count: post.word_count,
+        footnotes: post.footnotes.clone(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn write_epub_outputs(
-    output_dir: &Path,
+    output_paths: &mut OutputPaths,
     publication_title: &str,
     publication_author: &str,
+    book: BookIdentity<'_>,
     posts: &[PostContent],
-    metadata_fields: &HashSet<MetadataField>,
+    file_stems: &[String],
     granularity: &Granularity,
-    cover: Option<&CoverAsset>,
+    settings: &EpubSettings<'_>,
+    volume_limits: VolumeLimits,
+    warnings: &mut Vec<String>,
 ) -> Result<Vec<String>> {
     match granularity {
         Granularity::PerPost => posts
             .iter()
-            .map(|post| {
-                let filename = format!(
-                    "{} - {}.epub",
-                    sanitize_filename(publication_title),
-                    sanitize_filename(&post.summary.title)
-                );
-                let file_path = output_dir.join(filename);
-                write_epub(
-                    &file_path,
-                    &post.summary.title,
-                    post.summary.author.as_deref().unwrap_or(publication_author),
-                    std::slice::from_ref(post),
-                    metadata_fields,
-                    cover,
-                )?;
+            .zip(file_stems)
+            .map(|(post, stem)| {
+                let file_path = output_paths.allocate(stem, "epub");
+                if !output_paths.dry_run {
+                    write_epub(
+                        &file_path,
+                        &post.summary.title,
+                        post.summary.author.as_deref().unwrap_or(publication_author),
+                        std::slice::from_ref(post),
+                        settings,
+                        warnings,
+                    )?;
+                }
                 Ok(file_path.to_string_lossy().to_string())
             })
             .collect(),
         Granularity::Combined => {
-            let filename = format!("{} - combined.epub", sanitize_filename(publication_title));
-            let file_path = output_dir.join(filename);
-            write_epub(
-                &file_path,
-                publication_title,
-                publication_author,
-                posts,
-                metadata_fields,
-                cover,
-            )?;
-            Ok(vec![file_path.to_string_lossy().to_string()])
+            let volumes = split_into_volumes(posts, volume_limits);
+            if volumes.len() <= 1 {
+                let stem = format!("{} - combined", sanitize_filename(publication_title));
+                let file_path = output_paths.allocate(&stem, "epub");
+                if !output_paths.dry_run {
+                    write_epub(&file_path, book.title, book.author, posts, settings, warnings)?;
+                }
+                return Ok(vec![file_path.to_string_lossy().to_string()]);
+            }
+
+            volumes
+                .iter()
+                .enumerate()
+                .map(|(index, volume)| {
+                    let stem = format!("{} - Vol {}", sanitize_filename(publication_title), index + 1);
+                    let file_path = output_paths.allocate(&stem, "epub");
+                    if !output_paths.dry_run {
+                        write_epub(
+                            &file_path,
+                            &format!("{} (Vol. {})", book.title, index + 1),
+                            book.author,
+                            volume,
+                            settings,
+                            warnings,
+                        )?;
+                    }
+                    Ok(file_path.to_string_lossy().to_string())
+                })
+                .collect()
+        }
+    }
+}
+
+/// Splits the ordered posts into consecutive volumes. The byte size is estimated
+/// from each chapter's body markup; a single post over the limit gets its own volume.
+fn split_into_volumes(posts: &[PostContent], limits: VolumeLimits) -> Vec<&[PostContent]> {
+    if limits.max_posts.is_none() && limits.max_bytes.is_none() {
+        return vec![posts];
+    }
+    let mut volumes = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+    for (index, post) in posts.iter().enumerate() {
+        let size = post.epub_body.len();
+        let count = index - start;
+        let over_count = limits.max_posts.is_some_and(|max| count >= max);
+        let over_bytes = limits.max_bytes.is_some_and(|max| count > 0 && bytes + size > max);
+        if over_count || over_bytes {
+            volumes.push(&posts[start..index]);
+            start = index;
+            bytes = 0;
         }
+        bytes += size;
+    }
+    if start < posts.len() {
+        volumes.push(&posts[start..]);
     }
+    volumes
 }
 
 fn write_epub(
@@ -371,9 +1935,19 @@ fn write_epub(
     book_title: &str,
     book_author: &str,
     posts: &[PostContent],
-    metadata_fields: &HashSet<MetadataField>,
-    cover: Option<&CoverAsset>,
+    settings: &EpubSettings<'_>,
+    warnings: &mut Vec<String>,
 ) -> Result<()> {
+    let EpubSettings {
+        metadata_fields,
+        cover,
+        custom_css,
+        language,
+        publication_title,
+        publication_url,
+        include_title_page,
+        ..
+    } = *settings;
     let file = File::create(output_file).context("Failed to create EPUB file.")?;
     let mut zip = ZipWriter::new(file);
 
@@ -393,10 +1967,26 @@ fn write_epub(
 </container>"#,
     )?;
 
+    let mut chapters: Vec<(String, Vec<TocHeading>)> = Vec::new();
+    let mut endnotes = Vec::new();
+    for (index, post) in posts.iter().enumerate() {
+        let chapter_id = format!("chapter-{}", index + 1);
+        if settings.endnotes {
+            let (body, notes) = split_endnotes(&post.epub_body, &chapter_id);
+            if !notes.is_empty() {
+                endnotes.push((post.summary.title.as_str(), notes));
+            }
+            chapters.push(anchor_chapter_headings(&body, &chapter_id));
+        } else {
+            chapters.push(anchor_chapter_headings(&post.epub_body, &chapter_id));
+        }
+    }
+
     let mut manifest_items = Vec::new();
     let mut spine_items = Vec::new();
 
     manifest_items.push(r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#.to_string());
+    manifest_items.push(r#"<item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#.to_string());
 
     if let Some(cover) = cover {
         let cover_path = format!("OEBPS/images/cover.{}", cover.extension);
@@ -410,13 +2000,70 @@ fn write_epub(
         spine_items.push(r#"<itemref idref="cover-page"/>"#.to_string());
     }
 
-    for (index, _post) in posts.iter().enumerate() {
+    let logo_href = match settings.logo.filter(|_| include_title_page) {
+        Some(logo) => {
+            let href = format!("images/logo.{}", logo.extension);
+            zip.start_file(format!("OEBPS/{href}"), deflated)?;
+            zip.write_all(&logo.bytes)?;
+            manifest_items.push(format!(r#"<item id="logo" href="{href}" media-type="{}"/>"#, logo.media_type));
+            Some(href)
+        }
+        None => None,
+    };
+    if include_title_page {
+        manifest_items.push(r#"<item id="title-page" href="text/title.xhtml" media-type="application/xhtml+xml"/>"#.to_string());
+        spine_items.push(r#"<itemref idref="title-page"/>"#.to_string());
+    }
+
+    if let Some(css) = custom_css {
+        zip.start_file("OEBPS/styles/custom.css", deflated)?;
+        zip.write_all(css.as_bytes())?;
+        manifest_items.push(r#"<item id="custom-css" href="styles/custom.css" media-type="text/css"/>"#.to_string());
+    }
+
+    let mut avatar_hrefs: HashMap<&str, String> = HashMap::new();
+    for url in posts.iter().filter_map(|post| post.author_avatar_url.as_deref()) {
+        let Some(avatar) = settings.avatars.get(url).filter(|_| !avatar_hrefs.contains_key(url)) else {
+            continue;
+        };
+        let avatar_id = format!("avatar-{}", avatar_hrefs.len() + 1);
+        let href = format!("images/{avatar_id}.{}", avatar.extension);
+        zip.start_file(format!("OEBPS/{href}"), deflated)?;
+        zip.write_all(&avatar.bytes)?;
+        manifest_items.push(format!(
+            r#"<item id="{avatar_id}" href="{href}" media-type="{}"/>"#,
+            avatar.media_type
+        ));
+        avatar_hrefs.insert(url, href);
+    }
+
+    let groups = chapter_groups(posts, settings.grouping);
+    for (index, post) in posts.iter().enumerate() {
+        if let Some(group) = groups.iter().position(|(start, _)| *start == index) {
+            let group_id = format!("group-{}", group + 1);
+            manifest_items.push(format!(
+                r#"<item id="{group_id}" href="text/{group_id}.xhtml" media-type="application/xhtml+xml"/>"#
+            ));
+            spine_items.push(format!(r#"<itemref idref="{group_id}"/>"#));
+        }
         let chapter_id = format!("chapter-{}", index + 1);
+        // The audio player references the episode file remotely rather than embedding it.
+        let properties = if post.summary.audio_url.is_some() && !settings.kindle {
+            r#" properties="remote-resources""#
+        } else {
+            ""
+        };
         manifest_items.push(format!(
-            r#"<item id="{chapter_id}" href="text/{chapter_id}.xhtml" media-type="application/xhtml+xml"/>"#
+            r#"<item id="{chapter_id}" href="text/{chapter_id}.xhtml" media-type="application/xhtml+xml"{properties}/>"#
         ));
         spine_items.push(format!(r#"<itemref idref="{chapter_id}"/>"#));
     }
+    if !endnotes.is_empty() {
+        manifest_items.push(r#"<item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>"#.to_string());
+        spine_items.push(r#"<itemref idref="notes"/>"#.to_string());
+    }
+
+    let landmarks = book_landmarks(cover.is_some(), include_title_page, !posts.is_empty());
 
     zip.start_file("OEBPS/content.opf", deflated)?;
     let identifier = Uuid::new_v4();
@@ -427,21 +2074,44 @@ fn write_epub(
     <dc:identifier id="BookId">urn:uuid:{identifier}</dc:identifier>
     <dc:title>{}</dc:title>
     <dc:creator>{}</dc:creator>
-    <dc:language>en</dc:language>
+    <dc:language>{}</dc:language>
     <dc:date>{}</dc:date>
+    <meta property="dcterms:modified">{}</meta>{}{}{}
   </metadata>
   <manifest>
     {}
   </manifest>
-  <spine>
+  <spine toc="ncx"{}>
     {}
   </spine>
+  <guide>
+    {}
+  </guide>
 </package>"#,
         escape_xml(book_title),
         escape_xml(book_author),
-        Utc::now().to_rfc3339(),
+        escape_xml(language),
+        opf_timestamp(book_publication_date(posts)),
+        opf_timestamp(Utc::now()),
+        render_provenance(publication_title, publication_url),
+        render_subjects(posts, metadata_fields),
+        if cover.is_some() && settings.kindle {
+            // EPUB 2 cover convention, which Kindle conversion relies on.
+            "\n    <meta name=\"cover\" content=\"cover-image\"/>"
+        } else {
+            ""
+        },
         manifest_items.join("\n    "),
-        spine_items.join("\n    ")
+        if settings.rtl { r#" page-progression-direction="rtl""# } else { "" },
+        spine_items.join("\n    "),
+        landmarks
+            .iter()
+            .map(|landmark| format!(
+                r#"<reference type="{}" title="{}" href="{}"/>"#,
+                landmark.guide_type, landmark.label, landmark.href
+            ))
+            .collect::<Vec<_>>()
+            .join("\n    ")
     );
     zip.write_all(metadata_xml.as_bytes())?;
 
@@ -450,12 +2120,37 @@ fn write_epub(
     if cover.is_some() {
         nav_links.push(r#"<li><a href="text/cover.xhtml">Cover</a></li>"#.to_string());
     }
-    for (index, post) in posts.iter().enumerate() {
-        let chapter_id = format!("chapter-{}", index + 1);
-        nav_links.push(format!(
-            r#"<li><a href="text/{chapter_id}.xhtml">{}</a></li>"#,
-            escape_xml(&post.summary.title)
-        ));
+    if include_title_page {
+        nav_links.push(r#"<li><a href="text/title.xhtml">Title Page</a></li>"#.to_string());
+    }
+    let chapter_links = posts
+        .iter()
+        .zip(&chapters)
+        .enumerate()
+        .map(|(index, (post, (_, headings)))| {
+            let chapter_id = format!("chapter-{}", index + 1);
+            format!(
+                r#"<li><a href="text/{chapter_id}.xhtml">{}</a>{}</li>"#,
+                escape_xml(&post.summary.title),
+                render_nav_headings(&chapter_id, headings)
+            )
+        })
+        .collect::<Vec<_>>();
+    if groups.is_empty() {
+        nav_links.extend(chapter_links);
+    } else {
+        for (group, (start, label)) in groups.iter().enumerate() {
+            let end = groups.get(group + 1).map_or(chapter_links.len(), |(next, _)| *next);
+            nav_links.push(format!(
+                "<li><a href=\"text/group-{}.xhtml\">{}</a>\n        <ol>\n          {}\n        </ol>\n      </li>",
+                group + 1,
+                escape_xml(label),
+                chapter_links[*start..end].join("\n          ")
+            ));
+        }
+    }
+    if !endnotes.is_empty() {
+        nav_links.push(r#"<li><a href="text/notes.xhtml">Notes</a></li>"#.to_string());
     }
     let nav = format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -468,14 +2163,56 @@ fn write_epub(
     <ol>
       {}
     </ol>
-  </nav>
+  </nav>{}
 </body>
 </html>"#,
         escape_xml(book_title),
-        nav_links.join("\n      ")
+        nav_links.join("\n      "),
+        // Kindle conversion reads the OPF guide instead; the landmarks nav only adds warnings there.
+        if settings.kindle {
+            String::new()
+        } else {
+            format!(
+                r#"
+  <nav epub:type="landmarks" id="landmarks" hidden="hidden" xmlns:epub="http://www.idpf.org/2007/ops">
+    <h2>Guide</h2>
+    <ol>
+      {}
+    </ol>
+  </nav>"#,
+                landmarks
+                    .iter()
+                    .map(|landmark| {
+                        let href = landmark.href.strip_prefix("nav.xhtml").unwrap_or(landmark.href);
+                        format!(r#"<li><a epub:type="{}" href="{href}">{}</a></li>"#, landmark.epub_type, landmark.label)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n      ")
+            )
+        }
     );
     zip.write_all(nav.as_bytes())?;
 
+    // EPUB 2 table of contents for readers that ignore nav.xhtml.
+    zip.start_file("OEBPS/toc.ncx", deflated)?;
+    let mut ncx_entries = Vec::new();
+    if cover.is_some() {
+        ncx_entries.push(("text/cover.xhtml".to_string(), "Cover".to_string()));
+    }
+    if include_title_page {
+        ncx_entries.push(("text/title.xhtml".to_string(), "Title Page".to_string()));
+    }
+    for (index, post) in posts.iter().enumerate() {
+        if let Some(group) = groups.iter().position(|(start, _)| *start == index) {
+            ncx_entries.push((format!("text/group-{}.xhtml", group + 1), groups[group].1.clone()));
+        }
+        ncx_entries.push((format!("text/chapter-{}.xhtml", index + 1), post.summary.title.clone()));
+    }
+    if !endnotes.is_empty() {
+        ncx_entries.push(("text/notes.xhtml".to_string(), "Notes".to_string()));
+    }
+    zip.write_all(render_ncx(&identifier.to_string(), book_title, &ncx_entries).as_bytes())?;
+
     if let Some(cover) = cover {
         zip.start_file("OEBPS/text/cover.xhtml", deflated)?;
         let cover_page = format!(
@@ -486,7 +2223,7 @@ fn write_epub(
   <title>Cover</title>
   <style>
     body {{ text-align: center; font-family: sans-serif; }}
-    img {{ max-width: 95%; max-height: 70vh; margin-top: 1rem; }}
+    img {{ max-width: 95%; {}margin-top: 1rem; }}
     h1 {{ margin-top: 1.5rem; }}
   </style>
 </head>
@@ -496,6 +2233,7 @@ fn write_epub(
   <img src="../images/cover.{}" alt="Cover image" />
 </body>
 </html>"#,
+            if settings.kindle { "" } else { "max-height: 70vh; " },
             escape_xml(book_title),
             escape_xml(book_author),
             cover.extension
@@ -503,21 +2241,87 @@ fn write_epub(
         zip.write_all(cover_page.as_bytes())?;
     }
 
-    for (index, post) in posts.iter().enumerate() {
+    if include_title_page {
+        zip.start_file("OEBPS/text/title.xhtml", deflated)?;
+        let title_page =
+            render_title_page(book_title, book_author, publication_url, posts.len(), logo_href.as_deref());
+        zip.write_all(title_page.as_bytes())?;
+    }
+
+    for (index, (post, (body, _))) in posts.iter().zip(&chapters).enumerate() {
         let chapter_id = format!("chapter-{}", index + 1);
         zip.start_file(format!("OEBPS/text/{chapter_id}.xhtml"), deflated)?;
-        let chapter_markup = render_epub_chapter(post, metadata_fields);
+        let avatar_href = post
+            .author_avatar_url
+            .as_deref()
+            .and_then(|url| avatar_hrefs.get(url))
+            .map(String::as_str);
+        let mut chapter_markup = render_epub_chapter(post, body, avatar_href, settings);
+        if let Err(problem) = validate_xhtml(&chapter_markup) {
+            warnings.push(format!(
+                "\"{}\" produced malformed XHTML ({problem}); its chapter uses the plain-text version instead.",
+                post.summary.title
+            ));
+            let fallback = plain_text_chapter_body(&post.plain_text);
+            chapter_markup = render_epub_chapter(post, &fallback, avatar_href, settings);
+        }
         zip.write_all(chapter_markup.as_bytes())?;
     }
 
+    for (group, (_, label)) in groups.iter().enumerate() {
+        zip.start_file(format!("OEBPS/text/group-{}.xhtml", group + 1), deflated)?;
+        zip.write_all(render_group_page(label, settings).as_bytes())?;
+    }
+
+    if !endnotes.is_empty() {
+        zip.start_file("OEBPS/text/notes.xhtml", deflated)?;
+        zip.write_all(render_notes_page(&endnotes, settings).as_bytes())?;
+    }
+
     zip.finish()?;
     Ok(())
 }
 
-fn render_epub_chapter(post: &PostContent, metadata_fields: &HashSet<MetadataField>) -> String {
-    let title = escape_xml(&post.summary.title);
-    let metadata = render_epub_metadata(post, metadata_fields);
-    let body = &post.epub_body;
+fn render_provenance(publication_title: &str, publication_url: &str) -> String {
+    let mut out = String::new();
+    if !publication_title.trim().is_empty() {
+        out.push_str(&format!("\n    <dc:publisher>{}</dc:publisher>", escape_xml(publication_title.trim())));
+    }
+    if !publication_url.trim().is_empty() {
+        out.push_str(&format!("\n    <dc:source>{}</dc:source>", escape_xml(publication_url.trim())));
+    }
+    out
+}
+
+/// One `<dc:subject>` per distinct tag (case-insensitive, first spelling wins),
+/// capped so heavily tagged publications don't bloat the OPF.
+fn render_subjects(posts: &[PostContent], metadata_fields: &HashSet<MetadataField>) -> String {
+    if !metadata_fields.contains(&MetadataField::Tags) {
+        return String::new();
+    }
+    let mut seen = HashSet::new();
+    posts
+        .iter()
+        .filter_map(|post| post.summary.tags.as_ref())
+        .flatten()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.to_lowercase()))
+        .take(MAX_SUBJECTS_PER_BOOK)
+        .map(|tag| format!("\n    <dc:subject>{}</dc:subject>", escape_xml(tag)))
+        .collect()
+}
+
+fn render_title_page(
+    book_title: &str,
+    book_author: &str,
+    publication_url: &str,
+    post_count: usize,
+    logo_href: Option<&str>,
+) -> String {
+    let post_label = if post_count == 1 { "post" } else { "posts" };
+    let logo = logo_href
+        .map(|href| format!("\n  <p class=\"logo\"><img src=\"../{}\" alt=\"\"/></p>", escape_xml(href)))
+        .unwrap_or_default();
     format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE html>
@@ -525,26 +2329,530 @@ fn render_epub_chapter(post: &PostContent, metadata_fields: &HashSet<MetadataFie
 <head>
   <title>{title}</title>
   <style>
-    body {{ font-family: Georgia, \"Times New Roman\", serif; line-height: 1.78; font-size: 1.05rem; color: #202020; }}
-    .meta {{ background: #f4f4f4; border: 1px solid #ddd; padding: 0.75rem; margin-bottom: 1rem; }}
-    .meta p {{ margin: 0.2rem 0; font-size: 0.92rem; }}
-    section p {{ margin: 0 0 1.25em; }}
-    section h2, section h3 {{ margin-top: 1.7em; margin-bottom: 0.7em; }}
-    section ul, section ol {{ margin: 0.5em 0 1.25em 1.2em; }}
-    section li {{ margin-bottom: 0.5em; }}
-    section blockquote {{ margin: 1.2em 0; padding-left: 1em; border-left: 3px solid #cfd5e2; color: #444; }}
-    .footnote-ref {{ text-decoration: none; line-height: 0; }}
-    .footnote-ref-num {{ font-size: 0.72em; vertical-align: super; }}
-    .footnotes {{ border-top: 1px solid #ddd; margin-top: 2em; padding-top: 1em; }}
-    .footnotes li {{ margin-bottom: 0.6em; }}
+    body {{ text-align: center; font-family: Georgia, "Times New Roman", serif; }}
+    h1 {{ margin-top: 25vh; font-size: 2rem; }}
+    .author {{ font-size: 1.2rem; margin-top: 0.5rem; }}
+    .colophon {{ margin-top: 6rem; font-size: 0.85rem; color: #555; }}
+    .colophon p {{ margin: 0.2rem 0; }}
+    .logo {{ margin-top: 15vh; }}
+    .logo img {{ max-width: 6rem; max-height: 6rem; }}
+    .logo + h1 {{ margin-top: 1.5rem; }}
+  </style>
+</head>
+<body>{logo}
+  <h1>{title}</h1>
+  <p class="author">{author}</p>
+  <section class="colophon">
+    <p>{post_count} {post_label}</p>
+    <p>Source: {url}</p>
+    <p>Exported on {date}</p>
+    <p>All content remains the copyright of its original author.</p>
+  </section>
+</body>
+</html>"#,
+        title = escape_xml(book_title),
+        author = escape_xml(book_author),
+        url = escape_xml(publication_url),
+        date = Utc::now().format("%Y-%m-%d"),
+    )
+}
+
+/// Takes the footnotes section that `build_epub_body` appended to a chapter out of
+/// it, for the end-of-book notes page. Note ids are prefixed with the chapter id so
+/// they stay unique across the book, references in the chapter point into
+/// `notes.xhtml`, and each back link returns to the chapter file.
+fn split_endnotes(body: &str, chapter_id: &str) -> (String, Vec<String>) {
+    const SECTION_START: &str = "\n    <section class=\"footnotes\">";
+    const SECTION_END: &str = "\n      </ol>\n    </section>";
+    let Some(start) = body.find(SECTION_START) else {
+        return (body.to_string(), Vec::new());
+    };
+    let Some(end) = body[start..].find(SECTION_END).map(|offset| start + offset) else {
+        return (body.to_string(), Vec::new());
+    };
+    let section = &body[start..end];
+
+    let item_regex = Regex::new(r#"\n        <li id="footnote-(\d+)""#).expect("valid footnote-item regex");
+    let starts = item_regex.find_iter(section).map(|found| found.start()).collect::<Vec<_>>();
+    let notes = starts
+        .iter()
+        .enumerate()
+        .map(|(position, &item_start)| {
+            let item_end = starts.get(position + 1).copied().unwrap_or(section.len());
+            section[item_start..item_end]
+                .trim()
+                .replacen(r#"<li id="footnote-"#, &format!(r#"<li id="{chapter_id}-footnote-"#), 1)
+                .replace(r##"href="#footnote-ref-"##, &format!(r##"href="{chapter_id}.xhtml#footnote-ref-"##))
+        })
+        .collect();
+
+    let chapter = format!("{}{}", &body[..start], &body[end + SECTION_END.len()..]);
+    let ref_regex = Regex::new(r##"href="#footnote-(\d+)""##).expect("valid footnote-ref regex");
+    let chapter = ref_regex
+        .replace_all(&chapter, format!(r##"href="notes.xhtml#{chapter_id}-footnote-$1""##).as_str())
+        .into_owned();
+    (chapter, notes)
+}
+
+/// Divider page opening a group of chapters.
+fn render_group_page(label: &str, settings: &EpubSettings<'_>) -> String {
+    let direction = if settings.rtl { r#" dir="rtl""# } else { "" };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml"{direction}>
+<head>
+  <title>{label}</title>
+  <style>
+    body {{ text-align: center; font-family: Georgia, "Times New Roman", serif; }}
+    h1 {{ margin-top: 30%; font-size: 1.8em; }}
+  </style>
+</head>
+<body>
+  <h1 class="group-title">{label}</h1>
+</body>
+</html>"#,
+        label = escape_xml(label)
+    )
+}
+
+/// The end-of-book notes chapter: one numbered list per chapter that has notes.
+fn render_notes_page(endnotes: &[(&str, Vec<String>)], settings: &EpubSettings<'_>) -> String {
+    let sections = endnotes
+        .iter()
+        .map(|(title, notes)| {
+            let items = notes.join("\n      ");
+            let items = if settings.kindle { strip_epub_types(&items).into_owned() } else { items };
+            format!(
+                "<section class=\"endnotes\">\n    <h2>{}</h2>\n    <ol>\n      {items}\n    </ol>\n  </section>",
+                escape_xml(title)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n  ");
+    let direction = if settings.rtl { r#" dir="rtl""# } else { "" };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"{direction}>
+<head>
+  <title>Notes</title>
+  <style>
+    body {{ font-family: Georgia, "Times New Roman", serif; line-height: 1.6; }}
+    h2 {{ font-size: 1.1em; margin-top: 1.5em; }}
+    li {{ margin-bottom: 0.6em; }}
     .footnote-backref {{ text-decoration: none; font-size: 0.9em; }}
   </style>
 </head>
 <body>
-  <h1>{title}</h1>
+  <h1>Notes</h1>
+  {sections}
+</body>
+</html>"#
+    )
+}
+
+/// Gives every `<h2>`/`<h3>` in the chapter body a book-unique id (prefixed with the
+/// chapter id) and returns the rewritten body with the headings for the nav document.
+/// Ids the heading already carried, on itself or on Substack's nested anchor widget,
+/// are dropped, and `#fragment` links to them are pointed at the new id instead.
+/// The generated footnotes and comments appendices are left out of the TOC.
+fn anchor_chapter_headings(body: &str, chapter_id: &str) -> (String, Vec<TocHeading>) {
+    let appendix_start = ["<section class=\"footnotes\">", "<section class=\"comments\">"]
+        .iter()
+        .filter_map(|marker| body.find(marker))
+        .min();
+    let (content, appendix) = match appendix_start {
+        Some(position) => body.split_at(position),
+        None => (body, ""),
+    };
+    let heading_regex = Regex::new(r"(?is)<h([23])(\s[^>]*)?>(.*?)</h[23]>").expect("valid toc-heading regex");
+    let id_attr_regex = Regex::new(r#"(?i)\s+id\s*=\s*["']([^"']*)["']"#).expect("valid heading-id regex");
+    let mut headings = Vec::new();
+    let mut renamed_ids = HashMap::new();
+    let anchored = heading_regex
+        .replace_all(content, |caps: &regex::Captures| {
+            let original = caps.get(0).map(|m| m.as_str()).unwrap_or_default().to_string();
+            let level = if caps.get(1).map(|m| m.as_str()) == Some("2") { 2 } else { 3 };
+            let inner = caps.get(3).map(|m| m.as_str()).unwrap_or_default();
+            let title = scraper::Html::parse_fragment(inner)
+                .root_element()
+                .text()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            if title.is_empty() {
+                return original;
+            }
+            let attributes = caps.get(2).map(|m| m.as_str()).unwrap_or_default();
+            let id = format!("{chapter_id}-h{}", headings.len() + 1);
+            for old_id in id_attr_regex.captures_iter(attributes).chain(id_attr_regex.captures_iter(inner)) {
+                renamed_ids.insert(decode_fragment(&old_id[1]), id.clone());
+            }
+            let attributes = id_attr_regex.replace_all(attributes, "");
+            let inner = id_attr_regex.replace_all(inner, "");
+            let rewritten = format!(r#"<h{level} id="{id}"{attributes}>{inner}</h{level}>"#);
+            headings.push(TocHeading { level, id, title });
+            rewritten
+        })
+        .into_owned();
+    let anchored = format!("{anchored}{appendix}");
+    if renamed_ids.is_empty() {
+        return (anchored, headings);
+    }
+    let fragment_href_regex = Regex::new(r#"(?i)\bhref\s*=\s*["']#([^"']+)["']"#).expect("valid fragment-href regex");
+    let relinked = fragment_href_regex
+        .replace_all(&anchored, |caps: &regex::Captures| match renamed_ids.get(&decode_fragment(&caps[1])) {
+            Some(id) => format!(r##"href="#{id}""##),
+            None => caps[0].to_string(),
+        })
+        .into_owned();
+    (relinked, headings)
+}
+
+/// Percent-decodes an id or `#fragment`, so `#%C2%A7part-one` matches `id="§part-one"`.
+fn decode_fragment(value: &str) -> String {
+    let bytes = value.trim().as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes.get(index + 1..index + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.filter(|_| bytes[index] == b'%').and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A "Go to" target listed both in the nav landmarks and in the OPF `<guide>`.
+#[derive(Debug, Clone, Copy)]
+struct Landmark {
+    epub_type: &'static str,
+    guide_type: &'static str,
+    href: &'static str,
+    label: &'static str,
+}
+
+/// Landmarks for the pages the book actually has; hrefs are relative to `OEBPS/`.
+fn book_landmarks(has_cover: bool, has_title_page: bool, has_chapters: bool) -> Vec<Landmark> {
+    let mut landmarks = Vec::new();
+    if has_cover {
+        landmarks.push(Landmark {
+            epub_type: "cover",
+            guide_type: "cover",
+            href: "text/cover.xhtml",
+            label: "Cover",
+        });
+    }
+    if has_title_page {
+        landmarks.push(Landmark {
+            epub_type: "titlepage",
+            guide_type: "title-page",
+            href: "text/title.xhtml",
+            label: "Title Page",
+        });
+    }
+    landmarks.push(Landmark {
+        epub_type: "toc",
+        guide_type: "toc",
+        href: "nav.xhtml#toc",
+        label: "Table of Contents",
+    });
+    if has_chapters {
+        landmarks.push(Landmark {
+            epub_type: "bodymatter",
+            guide_type: "text",
+            href: "text/chapter-1.xhtml",
+            label: "Start of Content",
+        });
+    }
+    landmarks
+}
+
+/// Builds `toc.ncx` from `(href, label)` pairs, numbering `playOrder` from 1.
+fn render_ncx(identifier: &str, book_title: &str, entries: &[(String, String)]) -> String {
+    let nav_points = entries
+        .iter()
+        .enumerate()
+        .map(|(index, (href, label))| {
+            format!(
+                r#"<navPoint id="navpoint-{order}" playOrder="{order}">
+      <navLabel><text>{}</text></navLabel>
+      <content src="{}"/>
+    </navPoint>"#,
+                escape_xml(label),
+                escape_xml(href),
+                order = index + 1
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="urn:uuid:{identifier}"/>
+    <meta name="dtb:depth" content="1"/>
+    <meta name="dtb:totalPageCount" content="0"/>
+    <meta name="dtb:maxPageNumber" content="0"/>
+  </head>
+  <docTitle><text>{}</text></docTitle>
+  <navMap>
+    {}
+  </navMap>
+</ncx>"#,
+        escape_xml(book_title),
+        nav_points.join("\n    ")
+    )
+}
+
+/// Renders the nested `<ol>` placed under a chapter entry in `nav.xhtml`; `<h3>`
+/// entries nest under the preceding `<h2>`.
+fn render_nav_headings(chapter_id: &str, headings: &[TocHeading]) -> String {
+    if headings.is_empty() {
+        return String::new();
+    }
+    let link = |heading: &TocHeading| {
+        format!(
+            r#"<a href="text/{chapter_id}.xhtml#{}">{}</a>"#,
+            heading.id,
+            escape_xml(&heading.title)
+        )
+    };
+    let mut items: Vec<(String, Vec<String>)> = Vec::new();
+    for heading in headings {
+        match items.last_mut() {
+            Some((_, children)) if heading.level == 3 => children.push(format!("<li>{}</li>", link(heading))),
+            _ => items.push((link(heading), Vec::new())),
+        }
+    }
+    let rendered = items
+        .into_iter()
+        .map(|(parent, children)| {
+            if children.is_empty() {
+                format!("<li>{parent}</li>")
+            } else {
+                format!("<li>{parent}<ol>{}</ol></li>", children.join(""))
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("<ol>{}</ol>", rendered.join(""))
+}
+
+/// The newest post's date, so re-exporting the same posts yields the same
+/// `dc:date`; the export time is only used when no post date can be parsed.
+fn book_publication_date(posts: &[PostContent]) -> DateTime<Utc> {
+    posts
+        .iter()
+        .filter_map(|post| parse_datetime_flexible(&post.summary.published_at))
+        .max()
+        .unwrap_or_else(Utc::now)
+}
+
+fn opf_timestamp(value: DateTime<Utc>) -> String {
+    value.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Parses a chapter as strict XML, which is what EPUB readers do, and reports the
+/// first unbalanced tag, malformed attribute, or repeated id.
+fn validate_xhtml(markup: &str) -> std::result::Result<(), String> {
+    let mut reader = Reader::from_str(markup);
+    let mut open_tags = Vec::new();
+    let mut ids = HashSet::new();
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|error| format!("{error} at byte {}", reader.error_position()))?;
+        let tag = match event {
+            Event::Eof => break,
+            Event::Start(ref tag) | Event::Empty(ref tag) => tag,
+            Event::End(_) => {
+                open_tags.pop();
+                continue;
+            }
+            _ => continue,
+        };
+        for attribute in tag.attributes() {
+            let attribute = attribute.map_err(|error| error.to_string())?;
+            if attribute.key.as_ref() == b"id" {
+                let id = String::from_utf8_lossy(&attribute.value).into_owned();
+                if !ids.insert(id.clone()) {
+                    return Err(format!("duplicate id \"{id}\""));
+                }
+            }
+        }
+        if matches!(event, Event::Start(_)) {
+            open_tags.push(String::from_utf8_lossy(tag.name().as_ref()).into_owned());
+        }
+    }
+    match open_tags.last() {
+        Some(name) => Err(format!("unclosed <{name}>")),
+        None => Ok(()),
+    }
+}
+
+fn plain_text_chapter_body(plain_text: &str) -> String {
+    plain_text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|chunk| !chunk.is_empty())
+        .map(|chunk| format!("<p>{}</p>", escape_xml(chunk).replace('\n', "<br/>")))
+        .collect::<Vec<_>>()
+        .join("\n    ")
+}
+
+fn post_subtitle(post: &PostContent) -> Option<&str> {
+    post.summary.subtitle.as_deref().map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// Default chapter stylesheet, inlined into every chapter.
+const CHAPTER_CSS: &str = r#"    body { font-family: Georgia, "Times New Roman", serif; line-height: 1.78; font-size: 1.05rem; color: #202020; }
+    .meta { background: #f4f4f4; border: 1px solid #ddd; padding: 0.75rem; margin-bottom: 1rem; }
+    .meta p { margin: 0.2rem 0; font-size: 0.92rem; }
+    .subtitle { margin: -0.4em 0 1em; font-size: 1.15rem; font-weight: normal; font-style: italic; color: #555; }
+    .byline { margin: 0 0 1em; font-size: 0.95rem; color: #444; }
+    .byline img { width: 2.4em; height: 2.4em; border-radius: 50%; vertical-align: middle; margin-right: 0.5em; }
+    section p { margin: 0 0 1.25em; }
+    section h2, section h3 { margin-top: 1.7em; margin-bottom: 0.7em; }
+    section ul, section ol { margin: 0.5em 0 1.25em 1.2em; }
+    section li { margin-bottom: 0.5em; }
+    section blockquote { margin: 1.2em 0; padding-left: 1em; border-left: 3px solid #cfd5e2; color: #444; }
+    html[dir="rtl"] body { direction: rtl; unicode-bidi: embed; }
+    html[dir="rtl"] section ul, html[dir="rtl"] section ol { margin: 0.5em 1.2em 1.25em 0; }
+    html[dir="rtl"] section blockquote { padding-left: 0; padding-right: 1em; border-left: none; border-right: 3px solid #cfd5e2; }
+    .footnote-ref { text-decoration: none; line-height: 0; }
+    .footnote-ref-num { font-size: 0.72em; vertical-align: super; }
+    .footnotes { border-top: 1px solid #ddd; margin-top: 2em; padding-top: 1em; }
+    .footnotes li { margin-bottom: 0.6em; }
+    .footnote-backref { text-decoration: none; font-size: 0.9em; }
+    table { border-collapse: collapse; margin: 1em 0; width: 100%; font-size: 0.92rem; }
+    th, td { border: 1px solid #ccc; padding: 0.3em 0.5em; text-align: left; vertical-align: top; }
+    thead th { background: #f4f4f4; }
+    pre { white-space: pre; overflow-x: auto; background: #f6f6f6; border: 1px solid #e2e2e2; padding: 0.6em 0.8em; margin: 1em 0; line-height: 1.4; }
+    pre, code { font-family: Menlo, Consolas, "Courier New", monospace; font-size: 0.85rem; }
+    figure { margin: 1.2em 0; text-align: center; }
+    figure img { max-width: 100%; }
+    figcaption { font-style: italic; text-align: center; font-size: 0.9rem; color: #555; margin-top: 0.4em; }
+    .audio { margin: 1em 0 1.5em; }
+    .audio audio { width: 100%; }
+    .listen { font-weight: bold; font-size: 1.05rem; }
+    .comments { border-top: 1px solid #ddd; margin-top: 2em; padding-top: 1em; }
+    .comment { margin: 0.8em 0 0.8em 0; }
+    .comment .comment { margin-left: 1.2em; padding-left: 0.8em; border-left: 2px solid #e2e2e2; }
+    .comment p { margin: 0.2em 0; }
+    .comment-meta { font-size: 0.88rem; color: #555; }
+"#;
+
+/// Dark counterparts of the `CHAPTER_CSS` colors, for readers that honor
+/// `prefers-color-scheme`.
+const DARK_CHAPTER_CSS: &str = r#"    @media (prefers-color-scheme: dark) {
+      body { background: #121212; color: #e4e4e4; }
+      .meta, thead th { background: #1e1e1e; border-color: #3a3a3a; }
+      .subtitle, .byline, figcaption, .comment-meta { color: #b0b0b0; }
+      section blockquote { border-left-color: #4a5468; color: #c4c4c4; }
+      html[dir="rtl"] section blockquote { border-right-color: #4a5468; }
+      pre { background: #1a1a1a; border-color: #333; }
+      th, td { border-color: #444; }
+      .footnotes, .comments { border-top-color: #3a3a3a; }
+      .comment .comment { border-left-color: #333; }
+      a { color: #8ab4f8; }
+    }
+"#;
+
+/// Conservative chapter stylesheet for Kindle conversion: ems instead of rems,
+/// no viewport units, no attribute selectors.
+const KINDLE_CHAPTER_CSS: &str = r#"    body { font-family: serif; line-height: 1.5; }
+    .meta { border-top: 1px solid #999; border-bottom: 1px solid #999; padding: 0.4em 0; margin-bottom: 1em; }
+    .meta p { margin: 0.2em 0; font-size: 0.9em; }
+    .subtitle { margin: 0 0 1em; font-size: 1.1em; font-weight: normal; font-style: italic; }
+    .byline { margin: 0 0 1em; font-size: 0.95em; }
+    .byline img { width: 2.4em; height: 2.4em; vertical-align: middle; margin-right: 0.5em; }
+    p { margin: 0 0 1em; }
+    blockquote { margin: 1em 0 1em 1.5em; }
+    .footnote-ref-num { font-size: 0.75em; vertical-align: super; }
+    .footnotes { border-top: 1px solid #999; margin-top: 2em; padding-top: 1em; }
+    table { border-collapse: collapse; margin: 1em 0; }
+    th, td { border: 1px solid #999; padding: 0.2em 0.4em; }
+    pre { white-space: pre-wrap; font-family: monospace; font-size: 0.85em; }
+    code { font-family: monospace; }
+    figure { margin: 1em 0; text-align: center; }
+    img { max-width: 100%; }
+    figcaption { font-style: italic; font-size: 0.9em; }
+    .listen { font-weight: bold; }
+    .comments { border-top: 1px solid #999; margin-top: 2em; padding-top: 1em; }
+    .comment .comment { margin-left: 1.2em; }
+    .comment-meta { font-size: 0.88em; }
+"#;
+
+/// `avatar_href` is the book-relative path of the author's embedded avatar, if any.
+fn render_epub_chapter(post: &PostContent, body: &str, avatar_href: Option<&str>, settings: &EpubSettings<'_>) -> String {
+    let title = escape_xml(&post.summary.title);
+    let level = settings.title_heading_level;
+    let subtitle = post_subtitle(post)
+        .filter(|_| settings.subtitle_heading)
+        .map(|value| format!("\n  <h{sub} class=\"subtitle\">{}</h{sub}>", escape_xml(value), sub = level + 1))
+        .unwrap_or_default();
+    let byline = avatar_href
+        .map(|href| {
+            format!(
+                "\n  <p class=\"byline\"><img src=\"../{href}\" alt=\"\"/>{}</p>",
+                escape_xml(post.summary.author.as_deref().unwrap_or_default())
+            )
+        })
+        .unwrap_or_default();
+    let body = shift_headings(body, level - 1);
+    let body = if settings.kindle { strip_epub_types(&body) } else { body };
+    let metadata = render_epub_metadata(post, settings.metadata_fields);
+    let audio = post
+        .summary
+        .audio_url
+        .as_deref()
+        .map(|url| {
+            let url = escape_xml(url);
+            if settings.kindle {
+                return format!(r#"<p class="listen"><a href="{url}">Listen: {url}</a></p>"#);
+            }
+            format!(
+                r#"<section class="audio">
+    <audio controls="controls" src="{url}"><a href="{url}">Download audio</a></audio>
+    <p class="listen"><a href="{url}">Listen: {url}</a></p>
+  </section>"#
+            )
+        })
+        .unwrap_or_default();
+    // Linked after the inline defaults so user rules win on equal specificity.
+    let custom_css_link = if settings.custom_css.is_some() {
+        r#"<link rel="stylesheet" type="text/css" href="../styles/custom.css"/>"#
+    } else {
+        ""
+    };
+    let direction = if settings.rtl { r#" dir="rtl""# } else { "" };
+    let css = if settings.kindle { KINDLE_CHAPTER_CSS } else { CHAPTER_CSS };
+    let dark_css = if settings.dark_mode { DARK_CHAPTER_CSS } else { "" };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml"{direction}>
+<head>
+  <title>{title}</title>
+  <style>
+{css}{dark_css}  </style>
+  {custom_css_link}
+</head>
+<body>
+  <h{level}>{title}</h{level}>{subtitle}{byline}
   <section class="meta">
     {metadata}
   </section>
+  {audio}
   <section>
     {body}
   </section>
@@ -553,6 +2861,27 @@ fn render_epub_chapter(post: &PostContent, metadata_fields: &HashSet<MetadataFie
     )
 }
 
+/// Removes `epub:type` attributes, which Kindle converters warn about or choke on.
+fn strip_epub_types(body: &str) -> Cow<'_, str> {
+    let epub_type_regex = Regex::new(r#"\s+epub:type="[^"]*""#).expect("valid epub:type regex");
+    match epub_type_regex.replace_all(body, "") {
+        Cow::Borrowed(_) => Cow::Borrowed(body),
+        Cow::Owned(stripped) => Cow::Owned(stripped),
+    }
+}
+
+/// Moves every heading in `body` down by `shift` levels, stopping at `<h6>`.
+fn shift_headings(body: &str, shift: u8) -> Cow<'_, str> {
+    if shift == 0 {
+        return Cow::Borrowed(body);
+    }
+    let heading_tag_regex = Regex::new(r"(?i)<(/?)h([1-6])\b").expect("valid heading-tag regex");
+    heading_tag_regex.replace_all(body, |caps: &regex::Captures| {
+        let level = caps[2].parse::<u8>().unwrap_or(6).saturating_add(shift).min(6);
+        format!("<{}h{level}", &caps[1])
+    })
+}
+
 fn render_epub_metadata(post: &PostContent, metadata_fields: &HashSet<MetadataField>) -> String {
     let mut lines = Vec::new();
     if metadata_fields.contains(&MetadataField::Author) {
@@ -561,7 +2890,7 @@ fn render_epub_metadata(post: &PostContent, metadata_fields: &HashSet<MetadataFi
             escape_xml(post.summary.author.as_deref().unwrap_or("Unknown"))
         ));
     }
-    if metadata_fields.contains(&MetadataField::PublishedAt) {
+    if metadata_fields.contains(&MetadataField::PublishedAt) && !post.summary.published_at.trim().is_empty() {
         lines.push(format!(
             "<p><strong>Published:</strong> {}</p>",
             escape_xml(&post.summary.published_at)
@@ -573,6 +2902,24 @@ fn render_epub_metadata(post: &PostContent, metadata_fields: &HashSet<MetadataFi
             escape_xml(&post.summary.url)
         ));
     }
+    if metadata_fields.contains(&MetadataField::CanonicalUrl) {
+        lines.push(format!(
+            "<p><strong>Canonical URL:</strong> {}</p>",
+            escape_xml(canonical_url(&post.summary))
+        ));
+    }
+    if metadata_fields.contains(&MetadataField::Slug) {
+        lines.push(format!(
+            "<p><strong>Slug:</strong> {}</p>",
+            escape_xml(&post_slug(&post.summary.url, &post.summary.title))
+        ));
+    }
+    if metadata_fields.contains(&MetadataField::Section) {
+        lines.push(format!(
+            "<p><strong>Section:</strong> {}</p>",
+            escape_xml(post.summary.section.as_deref().unwrap_or("N/A"))
+        ));
+    }
     if metadata_fields.contains(&MetadataField::Tags) {
         lines.push(format!(
             "<p><strong>Tags:</strong> {}</p>",
@@ -603,6 +2950,15 @@ fn render_epub_metadata(post: &PostContent, metadata_fields: &HashSet<MetadataFi
             )
         ));
     }
+    if metadata_fields.contains(&MetadataField::WordCount) {
+        lines.push(format!("<p><strong>Word count:</strong> {}</p>", post.word_count));
+    }
+    if metadata_fields.contains(&MetadataField::Likes) {
+        lines.push(format!("<p><strong>Likes:</strong> {}</p>", format_count(post.summary.reaction_count)));
+    }
+    if metadata_fields.contains(&MetadataField::CommentCount) {
+        lines.push(format!("<p><strong>Comments:</strong> {}</p>", format_count(post.summary.comment_count)));
+    }
     if metadata_fields.contains(&MetadataField::Summary) {
         lines.push(format!(
             "<p><strong>Summary:</strong> {}</p>",
@@ -615,3 +2971,569 @@ fn render_epub_metadata(post: &PostContent, metadata_fields: &HashSet<MetadataFi
         lines.join("\n    ")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_post(title: &str, published_at: &str) -> PostContent {
+        PostContent {
+            summary: PostSummary {
+                id: title.to_string(),
+                title: title.to_string(),
+                published_at: published_at.to_string(),
+                url: format!("https://example.substack.com/p/{}", title.to_lowercase()),
+                author: Some("Dara Chaw".to_string()),
+                cover_image_url: None,
+                tags: None,
+                subtitle: None,
+                summary: None,
+                audio_url: None,
+                reaction_count: None,
+                comment_count: None,
+                section: None,
+                canonical_url: None,
+                reading_time_minutes: None,
+                content_html: None,
+            },
+            plain_text: String::new(),
+            epub_body: String::new(),
+            footnotes: Vec::new(),
+            reading_time_minutes: None,
+            word_count: 0,
+            summary_text: None,
+            paywalled: false,
+            truncated: false,
+            language: None,
+            substack_post_id: None,
+            author_avatar_url: None,
+            raw_html: None,
+        }
+    }
+
+    #[test]
+    fn filename_template_expands_tokens() {
+        let posts = vec![
+            sample_post("Welcome", "2023-04-01T10:00:00Z"),
+            sample_post("Welcome", "2023-05-01T10:00:00Z"),
+        ];
+        let stems = build_post_file_stems("{index} {date:%Y-%m} {title}", "Pub", &posts);
+        assert_eq!(stems, vec!["1 2023-04 Welcome", "2 2023-05 Welcome"]);
+    }
+
+    #[test]
+    fn undated_posts_show_no_publish_date() {
+        let fields = HashSet::from([MetadataField::PublishedAt]);
+        let dated = render_metadata_lines(&sample_post("Dated", "2024-03-01T00:00:00Z"), &fields);
+        assert!(dated.contains("Published: 2024-03-01T00:00:00Z"));
+        assert!(!render_metadata_lines(&sample_post("Undated", ""), &fields).contains("Published"));
+    }
+
+    #[test]
+    fn book_overrides_fall_back_to_the_publication() {
+        let mut request: ExportJobRequest = serde_json::from_value(serde_json::json!({
+            "publicationUrl": "https://example.substack.com",
+            "publicationTitle": "Example",
+            "publicationAuthor": "Dara Chaw",
+            "mode": "entire_profile",
+            "selectedPostIds": [],
+            "orderMode": "date",
+            "manualOrder": [],
+            "sortDirection": "desc",
+            "formats": ["epub"],
+            "granularity": "combined",
+            "metadataFields": [],
+            "outputDir": "/tmp/out",
+            "epub": { "bookTitleOverride": "Best of 2024", "bookAuthorOverride": " " }
+        }))
+        .unwrap();
+        assert_eq!(book_title_and_author(&request), ("Best of 2024", Some("Dara Chaw")));
+        request.epub.book_title_override = None;
+        request.publication_author = None;
+        assert_eq!(book_title_and_author(&request), ("Example", None));
+    }
+
+    #[test]
+    fn metadata_records_flatten_the_summary() {
+        let mut post = sample_post("Notes", "2024-01-01T00:00:00Z");
+        post.word_count = 420;
+        post.reading_time_minutes = Some(3);
+        post.summary.reading_time_minutes = Some(2);
+        post.footnotes = vec!["A source.".to_string()];
+        let text = serde_json::to_string(&build_metadata_record(&post)).unwrap();
+        assert_eq!(text.matches("readingTimeMinutes").count(), 1);
+        let json = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+        assert_eq!(json["title"], "Notes");
+        assert_eq!(json["url"], "https://example.substack.com/p/notes");
+        assert_eq!(json["slug"], "notes");
+        assert_eq!(json["wordCount"], 420);
+        assert_eq!(json["readingTimeMinutes"], 3);
+        assert_eq!(json["footnotes"], serde_json::json!(["A source."]));
+        assert!(json.get("plainText").is_none());
+    }
+
+    #[test]
+    fn chapter_headings_get_unique_ids_matching_the_nav() {
+        let body = r#"<p>Intro</p><h2 id="old">Part &amp; One</h2><h3>Detail</h3><h2>Part Two</h2>
+    <section class="footnotes">
+      <h2>Footnotes</h2>
+    </section>"#;
+        let (anchored, headings) = anchor_chapter_headings(body, "chapter-2");
+        assert_eq!(headings.len(), 3);
+        assert!(anchored.contains(r#"<h2 id="chapter-2-h1">Part &amp; One</h2>"#));
+        assert!(anchored.contains(r#"<h3 id="chapter-2-h2">Detail</h3>"#));
+        assert!(anchored.contains("<h2>Footnotes</h2>"));
+        assert!(!anchored.contains(r#"id="old""#));
+
+        let nav = render_nav_headings("chapter-2", &headings);
+        assert_eq!(
+            nav,
+            concat!(
+                r#"<ol><li><a href="text/chapter-2.xhtml#chapter-2-h1">Part &amp; One</a>"#,
+                r#"<ol><li><a href="text/chapter-2.xhtml#chapter-2-h2">Detail</a></li></ol></li>"#,
+                r#"<li><a href="text/chapter-2.xhtml#chapter-2-h3">Part Two</a></li></ol>"#
+            )
+        );
+    }
+
+    #[test]
+    fn section_links_follow_renamed_heading_ids() {
+        let body = r##"<p>Skip to <a href="#%C2%A7the-plan">the plan</a> or <a href="#intro">intro</a>.</p>
+<h2 class="header-anchor-post">The plan<div class="header-anchor-parent"><div id="§the-plan" class="header-anchor"></div></div></h2>
+<h3 id="details">Details</h3><p>See <a href="#details">above</a> and <a href="#footnote-1">1</a>.</p>"##;
+        let (anchored, _) = anchor_chapter_headings(body, "chapter-1");
+        assert!(anchored.contains(r##"<a href="#chapter-1-h1">the plan</a>"##));
+        assert!(anchored.contains(r##"<a href="#chapter-1-h2">above</a>"##));
+        assert!(anchored.contains(r##"<a href="#intro">intro</a>"##));
+        assert!(anchored.contains(r##"<a href="#footnote-1">1</a>"##));
+        assert!(!anchored.contains("§the-plan"));
+    }
+
+    #[test]
+    fn volumes_respect_post_count_and_size_limits() {
+        let mut posts = (1..=5)
+            .map(|n| sample_post(&format!("Post {n}"), "2024-01-01T00:00:00Z"))
+            .collect::<Vec<_>>();
+        let by_count = split_into_volumes(&posts, VolumeLimits { max_posts: Some(2), max_bytes: None });
+        assert_eq!(by_count.iter().map(|v| v.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+
+        for (index, post) in posts.iter_mut().enumerate() {
+            post.epub_body = "x".repeat(if index == 2 { 500 } else { 100 });
+        }
+        let by_size = split_into_volumes(&posts, VolumeLimits { max_posts: None, max_bytes: Some(250) });
+        assert_eq!(by_size.iter().map(|v| v.len()).collect::<Vec<_>>(), vec![2, 1, 2]);
+        assert_eq!(by_size[1][0].summary.title, "Post 3");
+    }
+
+    #[test]
+    fn output_paths_never_hand_out_the_same_file_twice() {
+        let mut paths = OutputPaths::new(PathBuf::from("out"));
+        assert_eq!(paths.allocate("Pub - Welcome", "txt"), PathBuf::from("out/Pub - Welcome.txt"));
+        assert_eq!(paths.allocate("Pub - welcome", "txt"), PathBuf::from("out/Pub - welcome (2).txt"));
+        assert_eq!(paths.allocate("Pub - Welcome (2)", "txt"), PathBuf::from("out/Pub - Welcome (2) (2).txt"));
+        assert_eq!(paths.allocate("Pub - Welcome", "epub"), PathBuf::from("out/Pub - Welcome.epub"));
+    }
+
+    #[test]
+    fn validate_xhtml_reports_unclosed_tags_and_duplicate_ids() {
+        assert!(validate_xhtml(r#"<html><body><p id="a">ok</p><br/></body></html>"#).is_ok());
+        assert!(validate_xhtml("<html><body><p>never closed</body></html>").is_err());
+        assert!(validate_xhtml("<html><body><div>left open</div>").is_err());
+        let duplicate = validate_xhtml(r#"<html><body><p id="a"/><p id="a"/></body></html>"#).unwrap_err();
+        assert!(duplicate.contains("duplicate id"));
+    }
+
+    #[test]
+    fn opf_dates_use_newest_post_and_whole_second_utc() {
+        let posts = vec![
+            sample_post("Older", "2023-04-01T10:00:00Z"),
+            sample_post("Newer", "Tue, 02 May 2023 08:30:15 +0200"),
+            sample_post("Undated", ""),
+        ];
+        assert_eq!(opf_timestamp(book_publication_date(&posts)), "2023-05-02T06:30:15Z");
+
+        let modified = opf_timestamp(Utc::now());
+        let pattern = Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z$").unwrap();
+        assert!(pattern.is_match(&modified), "{modified}");
+    }
+
+    #[test]
+    fn ncx_numbers_play_order_and_matches_book_id() {
+        let entries = vec![
+            ("text/cover.xhtml".to_string(), "Cover".to_string()),
+            ("text/chapter-1.xhtml".to_string(), "Fish & Chips".to_string()),
+        ];
+        let ncx = render_ncx("1234", "Book", &entries);
+        assert!(ncx.contains(r#"<meta name="dtb:uid" content="urn:uuid:1234"/>"#));
+        assert!(ncx.contains(r#"<navPoint id="navpoint-2" playOrder="2">"#));
+        assert!(ncx.contains("<text>Fish &amp; Chips</text>"));
+        assert!(validate_xhtml(&ncx).is_ok());
+    }
+
+    #[test]
+    fn transparent_webp_cover_becomes_png() {
+        let mut webp = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(600, 900, Rgba([10, 20, 30, 128])))
+            .write_to(&mut webp, ImageFormat::WebP)
+            .unwrap();
+        let mut warnings = Vec::new();
+        let cover = normalize_cover_asset(webp.into_inner(), None, DEFAULT_COVER_MAX_DIMENSION, &mut warnings).unwrap();
+        assert_eq!(cover.media_type, "image/png");
+        assert_eq!(image::guess_format(&cover.bytes).unwrap(), ImageFormat::Png);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn avif_covers_are_kept_with_a_warning() {
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf".to_vec();
+        let mut warnings = Vec::new();
+        let cover = normalize_cover_asset(avif.clone(), None, DEFAULT_COVER_MAX_DIMENSION, &mut warnings).unwrap();
+        assert_eq!(cover.bytes, avif);
+        assert_eq!(cover.media_type, "image/avif");
+        assert_eq!(warnings, ["Cover image is AVIF and cannot be converted; some e-readers may not show it."]);
+    }
+
+    #[test]
+    fn resizes_large_covers_and_pads_tiny_ones() {
+        let encode = |width, height| {
+            let mut png = std::io::Cursor::new(Vec::new());
+            DynamicImage::ImageRgb8(image::RgbImage::new(width, height))
+                .write_to(&mut png, ImageFormat::Png)
+                .unwrap();
+            png.into_inner()
+        };
+        let mut warnings = Vec::new();
+
+        let large = normalize_cover_asset(encode(1500, 2250), None, 1200, &mut warnings).unwrap();
+        let large = image::load_from_memory(&large.bytes).unwrap();
+        assert_eq!((large.width(), large.height()), (800, 1200));
+
+        let avatar = normalize_cover_asset(encode(150, 150), None, 1200, &mut warnings).unwrap();
+        assert_eq!(avatar.media_type, "image/jpeg");
+        let avatar = image::load_from_memory(&avatar.bytes).unwrap();
+        assert_eq!((avatar.width(), avatar.height()), (800, 1200));
+
+        let fine = encode(800, 1200);
+        assert_eq!(normalize_cover_asset(fine.clone(), None, 1200, &mut warnings).unwrap().bytes, fine);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn shifts_body_headings_below_demoted_titles() {
+        let body = r#"<h1>Intro</h1><h2 id="a">Part</h2><H6>Deep</H6>"#;
+        assert_eq!(shift_headings(body, 0), body);
+        assert_eq!(shift_headings(body, 1), r#"<h2>Intro</h2><h3 id="a">Part</h3><h6>Deep</h6>"#);
+    }
+
+    #[test]
+    fn date_range_filter_keeps_posts_inside_the_bounds() {
+        let posts = ["2023-12-31T23:00:00Z", "2024-01-15T08:00:00Z", "2024-02-01", "not a date"]
+            .map(|date| sample_post(date, date).summary)
+            .to_vec();
+        let mut warnings = Vec::new();
+        let kept = filter_by_date_range(posts.clone(), Some("2024-01-01"), Some("2024-01-31"), &mut warnings).unwrap();
+        assert_eq!(kept.iter().map(|post| post.title.as_str()).collect::<Vec<_>>(), ["2024-01-15T08:00:00Z"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("not a date"));
+
+        let mut warnings = Vec::new();
+        assert_eq!(filter_by_date_range(posts.clone(), None, Some(" "), &mut warnings).unwrap().len(), 4);
+        assert!(warnings.is_empty());
+        let error = filter_by_date_range(posts, Some("someday"), None, &mut warnings).unwrap_err();
+        assert_eq!(AppError::from(error).kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn tag_filter_matches_case_insensitively() {
+        let tagged = |title: &str, tags: Option<&[&str]>| PostSummary {
+            tags: tags.map(|tags| tags.iter().map(|tag| tag.to_string()).collect()),
+            ..sample_post(title, "2024-01-01T00:00:00Z").summary
+        };
+        let posts = vec![
+            tagged("Interview", Some(&["Interviews"])),
+            tagged("Sponsored interview", Some(&["interviews", "Sponsored"])),
+            tagged("Essay", Some(&["essays"])),
+            tagged("Unknown", None),
+        ];
+        let mut warnings = Vec::new();
+        let kept = filter_by_tags(posts.clone(), &["INTERVIEWS".to_string()], &["sponsored".to_string()], &mut warnings);
+        assert_eq!(kept.iter().map(|post| post.title.as_str()).collect::<Vec<_>>(), ["Interview"]);
+        assert_eq!(warnings.len(), 1);
+
+        let kept = filter_by_tags(posts, &[], &["essays".to_string()], &mut warnings);
+        assert_eq!(kept.len(), 3);
+    }
+
+    #[test]
+    fn title_filter_checks_title_and_subtitle() {
+        let post = |title: &str, subtitle: Option<&str>| PostSummary {
+            subtitle: subtitle.map(str::to_string),
+            ..sample_post(title, "2024-01-01T00:00:00Z").summary
+        };
+        let posts = vec![
+            post("On Gardening", None),
+            post("Weekly notes", Some("Mostly about the GARDEN")),
+            post("Weekly notes 2", Some("Cooking")),
+        ];
+        assert_eq!(filter_by_title(posts.clone(), Some("  ")).len(), 3);
+        let kept = filter_by_title(posts, Some("garden"));
+        assert_eq!(kept.iter().map(|post| post.title.as_str()).collect::<Vec<_>>(), ["On Gardening", "Weekly notes"]);
+    }
+
+    #[test]
+    fn parses_selection_ranges() {
+        assert_eq!(parse_selection_ranges("1-3, 5,2 ,7-7", 10).unwrap(), vec![0, 1, 2, 4, 6]);
+        for bad in ["0", "11", "4-2", "a", "1-", ", ,"] {
+            let error = AppError::from(parse_selection_ranges(bad, 10).unwrap_err());
+            assert_eq!(error.kind, ErrorKind::InvalidInput, "{bad}");
+        }
+    }
+
+    #[test]
+    fn landmarks_cover_only_existing_pages() {
+        let types = |landmarks: Vec<Landmark>| landmarks.iter().map(|landmark| landmark.epub_type).collect::<Vec<_>>();
+        assert_eq!(types(book_landmarks(true, true, true)), ["cover", "titlepage", "toc", "bodymatter"]);
+        assert_eq!(types(book_landmarks(false, false, true)), ["toc", "bodymatter"]);
+    }
+
+    #[test]
+    fn short_posts_are_dropped_from_output_and_succeeded() {
+        let with_words = |title: &str, word_count| PostContent {
+            word_count,
+            ..sample_post(title, "2024-01-01T00:00:00Z")
+        };
+        let mut contents = vec![with_words("Essay", 1_200), with_words("Housekeeping", 80)];
+        let mut succeeded = vec!["Essay".to_string(), "Housekeeping".to_string()];
+        let dropped = drop_contents(&mut contents, &mut succeeded, |content| content.word_count < 300);
+        assert_eq!(dropped, ["\"Housekeeping\""]);
+        assert_eq!(succeeded, ["Essay"]);
+        assert_eq!(contents.len(), 1);
+    }
+
+    /// Plain per-post settings; tests override the fields they exercise.
+    fn test_epub_settings<'a>(
+        metadata_fields: &'a HashSet<MetadataField>,
+        avatars: &'a HashMap<String, CoverAsset>,
+    ) -> EpubSettings<'a> {
+        EpubSettings {
+            metadata_fields,
+            cover: None,
+            custom_css: None,
+            language: "en",
+            publication_title: "Example",
+            publication_url: "https://example.substack.com",
+            include_title_page: false,
+            subtitle_heading: false,
+            rtl: false,
+            title_heading_level: 1,
+            avatars,
+            logo: None,
+            kindle: false,
+            endnotes: false,
+            grouping: ChapterGrouping::None,
+            dark_mode: false,
+        }
+    }
+
+    #[test]
+    fn kindle_chapters_drop_epub_types_and_audio_players() {
+        let (metadata_fields, avatars) = (HashSet::new(), HashMap::new());
+        let settings = EpubSettings {
+            kindle: true,
+            ..test_epub_settings(&metadata_fields, &avatars)
+        };
+        let mut post = sample_post("Episode", "2024-01-01T00:00:00Z");
+        post.summary.audio_url = Some("https://example.com/episode.mp3".to_string());
+        let body = r##"<p>Text<a class="footnote-ref" href="#footnote-1" epub:type="noteref">1</a></p>"##;
+
+        let chapter = render_epub_chapter(&post, body, None, &settings);
+        assert!(!chapter.contains("epub:type"));
+        assert!(!chapter.contains("<audio"));
+        assert!(!chapter.contains("rem;"));
+        assert!(chapter.contains(r##"<a class="footnote-ref" href="#footnote-1">1</a>"##));
+        assert!(validate_xhtml(&chapter).is_ok());
+
+        let standard = render_epub_chapter(&post, body, None, &EpubSettings { kindle: false, ..settings });
+        assert!(standard.contains(r#"epub:type="noteref""#));
+        assert!(standard.contains("<audio"));
+    }
+
+    #[test]
+    fn dark_mode_css_is_opt_in() {
+        let (metadata_fields, avatars) = (HashSet::new(), HashMap::new());
+        let settings = test_epub_settings(&metadata_fields, &avatars);
+        let post = sample_post("Night", "2024-01-01T00:00:00Z");
+
+        let light = render_epub_chapter(&post, "<p>Text</p>", None, &settings);
+        assert!(!light.contains("prefers-color-scheme"));
+        let dark = render_epub_chapter(&post, "<p>Text</p>", None, &EpubSettings { dark_mode: true, ..settings });
+        assert!(dark.contains("@media (prefers-color-scheme: dark)"));
+        assert!(validate_xhtml(&dark).is_ok());
+    }
+
+    #[test]
+    fn txt_toc_entries_match_post_markers() {
+        let posts = [
+            sample_post("First  post", "2024-01-01T00:00:00Z"),
+            sample_post("Second", "2024-01-02T00:00:00Z"),
+        ];
+        assert_eq!(render_txt_toc(&posts), "Contents\n[1] First post\n[2] Second\n\n");
+        assert_eq!(format!("## {}", txt_toc_label(1, &posts[1])), "## [2] Second");
+    }
+
+    #[test]
+    fn raw_html_index_links_are_percent_encoded() {
+        assert_eq!(encode_path_segment("Notes - Café #1.html"), "Notes%20-%20Caf%C3%A9%20%231.html");
+    }
+
+    #[test]
+    fn endnotes_move_to_the_notes_page_with_chapter_ids() {
+        let body = concat!(
+            r##"<p>Claim<a class="footnote-ref" href="#footnote-1" id="footnote-ref-1" epub:type="noteref">"##,
+            r##"<sup class="footnote-ref-num">1</sup></a>.</p>"##,
+            "\n    <section class=\"footnotes\">",
+            "\n      <h2>Footnotes</h2>",
+            "\n      <ol>",
+            r##"
+        <li id="footnote-1"><p>Source. <a class="footnote-backref" href="#footnote-ref-1" epub:type="backlink">[back]</a></p></li>"##,
+            "\n      </ol>",
+            "\n    </section>",
+            "\n    <section class=\"comments\"></section>"
+        );
+        let (chapter, notes) = split_endnotes(body, "chapter-2");
+        assert!(!chapter.contains("class=\"footnotes\""));
+        assert!(chapter.contains(r#"href="notes.xhtml#chapter-2-footnote-1""#));
+        assert!(chapter.ends_with("<section class=\"comments\"></section>"));
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].starts_with(r#"<li id="chapter-2-footnote-1">"#));
+        assert!(notes[0].contains(r##"href="chapter-2.xhtml#footnote-ref-1""##));
+
+        let (untouched, none) = split_endnotes("<p>No notes.</p>", "chapter-1");
+        assert_eq!(untouched, "<p>No notes.</p>");
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn republished_posts_keep_the_earliest_copy() {
+        let essay = (0..400).map(|index| format!("word{index}")).collect::<Vec<_>>().join(" ");
+        let with_text = |title: &str, published_at: &str, text: &str| PostContent {
+            plain_text: text.to_string(),
+            ..sample_post(title, published_at)
+        };
+        let contents = vec![
+            with_text("Reposted", "2024-05-01T00:00:00Z", &format!("{essay} Republished with a new intro line.")),
+            with_text("Original", "2023-01-01T00:00:00Z", &essay.to_uppercase()),
+            with_text("Different", "2024-02-01T00:00:00Z", "Something else entirely, not the same essay at all."),
+        ];
+        assert_eq!(find_duplicate_contents(&contents), [("Reposted".to_string(), "Original".to_string())]);
+    }
+
+    #[test]
+    fn manual_order_mismatches_are_reported() {
+        let posts = ["Alpha", "Beta", "Gamma"]
+            .map(|title| sample_post(title, "2024-01-01T00:00:00Z").summary)
+            .to_vec();
+        let manual_order = ["Beta", "Gone", "Alpha", "Gone"].map(str::to_string);
+        let mut warnings = Vec::new();
+        check_manual_order(&posts, &manual_order, &mut warnings);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("Ignored 1 id(s)") && warnings[0].ends_with(": Gone"));
+        assert!(warnings[1].ends_with(": \"Gamma\""));
+
+        warnings.clear();
+        check_manual_order(&posts, &["Gamma", "Alpha", "Beta"].map(str::to_string), &mut warnings);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn incremental_exports_pick_up_only_newer_posts() {
+        let posts = [
+            ("Old", "2024-01-01T00:00:00Z"),
+            ("Same", "2024-02-01T00:00:00Z"),
+            ("New", "2024-03-01T00:00:00Z"),
+            ("Linked", ""),
+        ]
+        .map(|(title, date)| sample_post(title, date).summary)
+        .to_vec();
+        let dir = std::env::temp_dir().join(format!("last-exports-{}", Uuid::new_v4()));
+        let mut last_exports = LastExports::load(&dir);
+        assert!(last_exports.newest_post_date("https://example.substack.com").is_none());
+        last_exports.record("https://Example.substack.com/", "2024-02-01T00:00:00+00:00");
+        last_exports.save(&dir).unwrap();
+
+        let since = LastExports::load(&dir)
+            .newest_post_date("https://example.substack.com")
+            .and_then(parse_datetime_flexible)
+            .unwrap();
+        let newer = filter_newer_than(posts, since);
+        assert_eq!(newer.iter().map(|post| post.title.as_str()).collect::<Vec<_>>(), ["New", "Linked"]);
+
+        reset_last_exports(&dir, None).unwrap();
+        assert!(LastExports::load(&dir).newest_post_date("https://example.substack.com").is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn resume_state_saves_one_file_per_changed_post() {
+        let dir = std::env::temp_dir().join(format!("resume-state-{}", Uuid::new_v4()));
+        let output = dir.join("first.txt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&output, "text").unwrap();
+        let output = output.to_string_lossy().to_string();
+
+        let mut state = ExportState::load(&dir);
+        state.record_content(&sample_post("First", "2024-01-01T00:00:00Z"));
+        state.record_output("First", &output);
+        state.record_output("Second", "missing.txt");
+        state.save(&dir).unwrap();
+        assert_eq!(fs::read_dir(dir.join(".export-state")).unwrap().count(), 2);
+
+        let loaded = ExportState::load(&dir);
+        assert_eq!(loaded.cached_content("First").map(|post| post.summary.title.as_str()), Some("First"));
+        assert_eq!(loaded.completed_outputs("First", &["txt"]), Some([output].as_slice()));
+        assert!(loaded.completed_outputs("Second", &["txt"]).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reading_lists_keep_their_order() {
+        let urls = [
+            "https://one.substack.com/p/second?utm_source=share",
+            "https://example.com/not-a-post",
+            "two.substack.com/p/first",
+            "https://one.substack.com/p/second",
+        ]
+        .map(str::to_string);
+        let mut warnings = Vec::new();
+        let posts = reading_list_posts(&urls, &mut warnings);
+        let ids = posts.iter().map(|post| post.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["https://one.substack.com/p/second", "https://two.substack.com/p/first"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].ends_with("https://example.com/not-a-post"));
+    }
+
+    #[test]
+    fn chapters_group_by_year_and_section() {
+        let mut posts = vec![
+            sample_post("One", "2023-05-01T00:00:00Z"),
+            sample_post("Two", "2023-09-01T00:00:00Z"),
+            sample_post("Three", "2024-01-01T00:00:00Z"),
+            sample_post("Four", "not a date"),
+        ];
+        let years = chapter_groups(&posts, ChapterGrouping::Year);
+        assert_eq!(years, vec![(0, "2023".to_string()), (2, "2024".to_string()), (3, "Undated".to_string())]);
+        assert_eq!(group_label(&posts[1].summary, ChapterGrouping::Month).as_deref(), Some("September 2023"));
+        assert!(chapter_groups(&posts, ChapterGrouping::None).is_empty());
+
+        posts[0].summary.section = Some("Essays".to_string());
+        posts[2].summary.section = Some("Essays".to_string());
+        let sections = chapter_groups(&posts, ChapterGrouping::Section);
+        assert_eq!(sections.iter().map(|(start, _)| *start).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(sections[1].1, "Other");
+
+        let mut warnings = Vec::new();
+        assert_eq!(resolve_group_by(Some(" Section "), &mut warnings), ChapterGrouping::Section);
+        assert_eq!(resolve_group_by(Some("weekday"), &mut warnings), ChapterGrouping::None);
+        assert_eq!(warnings.len(), 1);
+    }
+}
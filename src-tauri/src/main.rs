@@ -1,30 +1,105 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod cache;
+mod cover;
+mod error;
 mod export;
 mod models;
+mod resume;
 mod substack;
 mod utils;
 
-use models::{ExportJobRequest, ExportJobResult, PublicationRequest, PublicationResponse};
+use error::{AppError, ErrorKind};
+use models::{
+    ExportJobRequest, ExportJobResult, PostPreview, PublicationCheck, PublicationRequest, PublicationResponse,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{Emitter, Manager};
+
+/// Environment variable holding the log filter, e.g. `debug` or
+/// `substack_downloader=trace`; progress is logged at `info` when it is unset.
+const LOG_ENV_VAR: &str = "SUBSTACK_DOWNLOADER_LOG";
+
+/// Set by `cancel_export`; the running export checks it between posts and retries.
+#[derive(Default)]
+struct ExportCancellation(AtomicBool);
 
 #[tauri::command]
-async fn load_publication_posts(request: PublicationRequest) -> Result<PublicationResponse, String> {
+async fn load_publication_posts(request: PublicationRequest) -> Result<PublicationResponse, AppError> {
     substack::load_publication_posts(request)
         .await
-        .map_err(|error| error.to_string())
+        .map_err(AppError::from)
+}
+
+/// Emits `post-discovered` with each page of posts as it is parsed, then
+/// `discovery-complete` with the number of posts found.
+#[tauri::command]
+async fn stream_publication_posts(
+    app: tauri::AppHandle,
+    request: PublicationRequest,
+) -> Result<PublicationResponse, AppError> {
+    let response = substack::stream_publication_posts(request, |posts| {
+        let _ = app.emit("post-discovered", posts);
+    })
+    .await
+    .map_err(AppError::from)?;
+    let _ = app.emit("discovery-complete", response.posts.len());
+    Ok(response)
 }
 
 #[tauri::command]
-async fn run_export_job(request: ExportJobRequest) -> Result<ExportJobResult, String> {
-    export::run_export_job(request)
+async fn verify_publication(request: PublicationRequest) -> Result<PublicationCheck, AppError> {
+    substack::verify_publication(request).await.map_err(AppError::from)
+}
+
+/// Shows what the exports would make of one post, for debugging extraction.
+#[tauri::command]
+async fn preview_post(request: PublicationRequest) -> Result<PostPreview, AppError> {
+    substack::preview_post(request).await.map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn run_export_job(
+    app: tauri::AppHandle,
+    request: ExportJobRequest,
+    cancellation: tauri::State<'_, ExportCancellation>,
+) -> Result<ExportJobResult, AppError> {
+    cancellation.0.store(false, Ordering::SeqCst);
+    let state_dir = app.path().app_data_dir().ok();
+    export::run_export_job(request, &cancellation.0, state_dir.as_deref())
         .await
-        .map_err(|error| error.to_string())
+        .map_err(AppError::from)
+}
+
+/// Clears the dates incremental exports start from, for one publication or all.
+#[tauri::command]
+fn reset_incremental_state(app: tauri::AppHandle, publication_url: Option<String>) -> Result<(), AppError> {
+    let state_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error| AppError::new(ErrorKind::Io, format!("App data directory unavailable: {error}")))?;
+    export::reset_last_exports(&state_dir, publication_url.as_deref()).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn cancel_export(cancellation: tauri::State<'_, ExportCancellation>) {
+    cancellation.0.store(true, Ordering::SeqCst);
 }
 
 fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().filter_or(LOG_ENV_VAR, "info")).init();
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
-        .invoke_handler(tauri::generate_handler![load_publication_posts, run_export_job])
+        .manage(ExportCancellation::default())
+        .invoke_handler(tauri::generate_handler![
+            load_publication_posts,
+            stream_publication_posts,
+            verify_publication,
+            preview_post,
+            run_export_job,
+            reset_incremental_state,
+            cancel_export
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
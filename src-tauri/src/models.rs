@@ -4,6 +4,11 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 pub struct PublicationRequest {
     pub url: String,
+    pub substack_session_cookie: Option<String>,
+    /// `http://`, `https://` or `socks5://` proxy for every request.
+    pub proxy_url: Option<String>,
+    /// Per-request timeout; 30 seconds when absent.
+    pub request_timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +18,10 @@ pub struct PublicationInfo {
     pub title: String,
     pub author: Option<String>,
     pub author_cover_url: Option<String>,
+    pub language: Option<String>,
+    /// The site icon from the publication's home page, shown on EPUB title pages.
+    #[serde(default)]
+    pub publication_logo_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +36,24 @@ pub struct PostSummary {
     pub tags: Option<Vec<String>>,
     pub subtitle: Option<String>,
     pub summary: Option<String>,
+    pub audio_url: Option<String>,
+    #[serde(default)]
+    pub reaction_count: Option<u64>,
+    #[serde(default)]
+    pub comment_count: Option<u64>,
+    /// Publication section the post belongs to, such as "Podcast".
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Canonical address the post page declares, which differs from `url` for
+    /// cross-posts and custom domains.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    /// Reading time Substack's own post data reports, when it carried one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading_time_minutes: Option<u32>,
+    /// Full body from the feed's `content:encoded`, when the feed carried one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +63,35 @@ pub struct PublicationResponse {
     pub posts: Vec<PostSummary>,
 }
 
+/// One post run through the export pipeline by `preview_post`, as the exports
+/// would write it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostPreview {
+    pub title: String,
+    pub author: Option<String>,
+    pub plain_text: String,
+    pub epub_body: String,
+    pub footnote_count: usize,
+    pub truncated: bool,
+    pub word_count: usize,
+}
+
+/// Outcome of `verify_publication`, a quick check made before loading every post.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicationCheck {
+    /// Origin the publication is served from after redirects.
+    pub url: String,
+    pub reachable: bool,
+    pub title: Option<String>,
+    /// Posts listed by the feed or the first archive page; older posts are not counted.
+    pub estimated_post_count: Option<usize>,
+    pub custom_domain: bool,
+    /// `"feed"` or `"archive"`, whichever listed the posts.
+    pub source: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ExportMode {
@@ -62,6 +118,9 @@ pub enum SortDirection {
 pub enum ExportFormat {
     Epub,
     Txt,
+    Json,
+    /// Each post page exactly as downloaded, plus an index page.
+    RawHtml,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,11 +130,13 @@ pub enum Granularity {
     Combined,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CoverMode {
+    #[default]
     SubstackAuthor,
     Custom,
+    Generated,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -85,10 +146,18 @@ pub enum MetadataField {
     Author,
     PublishedAt,
     Url,
+    /// The `/p/<slug>` segment of the post URL.
+    Slug,
+    /// The page's declared canonical URL, falling back to the fetched URL.
+    CanonicalUrl,
+    Section,
     Tags,
     Subtitle,
     ReadingTime,
     Summary,
+    WordCount,
+    Likes,
+    CommentCount,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +167,11 @@ pub struct ExportJobRequest {
     pub publication_title: String,
     pub publication_author: Option<String>,
     pub author_cover_url: Option<String>,
+    #[serde(default)]
+    pub publication_logo_url: Option<String>,
+    /// Language the publication declares; posts built from the feed take it as theirs.
+    #[serde(default)]
+    pub publication_language: Option<String>,
     pub mode: ExportMode,
     pub selected_post_ids: Vec<String>,
     pub order_mode: OrderMode,
@@ -105,11 +179,187 @@ pub struct ExportJobRequest {
     pub sort_direction: SortDirection,
     pub formats: Vec<ExportFormat>,
     pub granularity: Granularity,
-    pub cover_mode: CoverMode,
-    pub custom_cover_data_url: Option<String>,
     pub metadata_fields: Vec<MetadataField>,
     pub output_dir: String,
+    #[serde(default)]
     pub posts: Vec<PostSummary>,
+    /// Post links, possibly from several publications, exported in this order in
+    /// place of `posts`; mode, selection and listing filters do not apply to them.
+    #[serde(default)]
+    pub post_urls: Vec<String>,
+    pub substack_session_cookie: Option<String>,
+    /// `http://`, `https://` or `socks5://` proxy for every request.
+    pub proxy_url: Option<String>,
+    /// Per-request timeout; 30 seconds when absent. Timed-out requests are retried.
+    pub request_timeout_secs: Option<u64>,
+    pub published_after: Option<String>,
+    pub published_before: Option<String>,
+    pub retry_count: Option<usize>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub max_requests_per_second: Option<f64>,
+    #[serde(default)]
+    pub include_comments: bool,
+    #[serde(default)]
+    pub resume: bool,
+    /// Only export posts newer than the newest one of the last successful export of
+    /// this publication, then remember the new newest date.
+    #[serde(default)]
+    pub incremental: bool,
+    #[serde(default)]
+    pub use_cache: bool,
+    pub cache_ttl_hours: Option<u64>,
+    #[serde(default)]
+    pub refresh_cache: bool,
+    /// Build posts from the feed's `content:encoded` instead of fetching each page.
+    /// Pages are still fetched when byline images are wanted, as the feed has none.
+    #[serde(default)]
+    pub use_feed_content: bool,
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+    /// Case-insensitive substring the title or subtitle must contain.
+    pub title_contains: Option<String>,
+    /// Case-insensitive text the downloaded body must contain; checked after fetching.
+    pub search_query: Option<String>,
+    /// 1-based positions in `posts` such as `1-10,15,20-25`, as an alternative to
+    /// `selected_post_ids` in specific-posts mode.
+    pub selection_ranges: Option<String>,
+    /// Posts with fewer words are dropped after fetching.
+    pub min_word_count: Option<usize>,
+    /// Drop posts whose text repeats an earlier post (republished under a new slug).
+    #[serde(default)]
+    pub dedupe_content: bool,
+    /// Abort the whole export, writing nothing, as soon as one post fails to download.
+    #[serde(default)]
+    pub strict_mode: bool,
+    #[serde(default)]
+    pub epub: EpubOptions,
+    #[serde(default)]
+    pub output: OutputOptions,
+    /// Settings older clients still send at the top level; `apply_legacy_options`
+    /// moves them into `epub` and `output`.
+    #[serde(flatten, skip_serializing)]
+    pub legacy: LegacyOptions,
+}
+
+impl ExportJobRequest {
+    /// Moves top-level settings from payloads predating the `epub` and `output`
+    /// groups into them. A value already set inside a group wins.
+    pub fn apply_legacy_options(&mut self) {
+        let legacy = std::mem::take(&mut self.legacy);
+        let (epub, output) = (&mut self.epub, &mut self.output);
+        epub.cover_mode = epub.cover_mode.take().or(legacy.cover_mode);
+        epub.custom_cover_data_url = epub.custom_cover_data_url.take().or(legacy.custom_cover_data_url);
+        epub.cover_max_dimension = epub.cover_max_dimension.or(legacy.cover_max_dimension);
+        epub.cover_background = epub.cover_background.take().or(legacy.cover_background);
+        epub.custom_css = epub.custom_css.take().or(legacy.custom_css);
+        epub.language = epub.language.take().or(legacy.language);
+        epub.include_title_page = epub.include_title_page.or(legacy.include_title_page);
+        epub.reading_direction = epub.reading_direction.take().or(legacy.reading_direction);
+        epub.chapter_order = epub.chapter_order.take().or(legacy.chapter_order);
+        epub.combined_heading_level = epub.combined_heading_level.or(legacy.combined_heading_level);
+        epub.max_posts_per_volume = epub.max_posts_per_volume.or(legacy.max_posts_per_volume);
+        epub.max_bytes_per_volume = epub.max_bytes_per_volume.or(legacy.max_bytes_per_volume);
+        output.filename_template = output.filename_template.take().or(legacy.filename_template);
+        output.dry_run |= legacy.dry_run.unwrap_or(false);
+        output.render_subtitle_heading |= legacy.render_subtitle_heading.unwrap_or(false);
+        output.plaintext_link_style = output.plaintext_link_style.take().or(legacy.plaintext_link_style);
+    }
+}
+
+/// The settings `ExportJobRequest` carried at the top level before they were
+/// grouped into `EpubOptions` and `OutputOptions`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LegacyOptions {
+    pub cover_mode: Option<CoverMode>,
+    pub custom_cover_data_url: Option<String>,
+    pub cover_max_dimension: Option<u32>,
+    pub cover_background: Option<String>,
+    pub custom_css: Option<String>,
+    pub language: Option<String>,
+    pub include_title_page: Option<bool>,
+    pub reading_direction: Option<String>,
+    pub chapter_order: Option<SortDirection>,
+    pub combined_heading_level: Option<u8>,
+    pub max_posts_per_volume: Option<usize>,
+    pub max_bytes_per_volume: Option<usize>,
+    pub filename_template: Option<String>,
+    pub dry_run: Option<bool>,
+    pub render_subtitle_heading: Option<bool>,
+    pub plaintext_link_style: Option<String>,
+}
+
+/// Settings that only affect EPUB files. A payload without the group, or with
+/// only some of its fields, gets the defaults for everything left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EpubOptions {
+    /// Where the cover comes from (default: the Substack author image).
+    pub cover_mode: Option<CoverMode>,
+    pub custom_cover_data_url: Option<String>,
+    /// Longest cover side in pixels; larger covers are scaled down (default 1600).
+    pub cover_max_dimension: Option<u32>,
+    /// `#rrggbb` background of generated covers.
+    pub cover_background: Option<String>,
+    pub custom_css: Option<String>,
+    pub language: Option<String>,
+    pub include_title_page: Option<bool>,
+    /// `"ltr"` or `"rtl"`; when absent the direction follows the book language.
+    pub reading_direction: Option<String>,
+    /// Chapter order inside combined EPUBs; falls back to `sort_direction`.
+    /// Ignored when `order_mode` is manual, since the hand-picked order wins.
+    pub chapter_order: Option<SortDirection>,
+    /// Heading level (1-5) of post titles in combined EPUBs; headings inside the
+    /// posts move down by the same amount. Per-post EPUBs always use `<h1>`.
+    pub combined_heading_level: Option<u8>,
+    pub max_posts_per_volume: Option<usize>,
+    pub max_bytes_per_volume: Option<usize>,
+    /// Shows the post author's avatar and name at the top of each chapter.
+    pub include_byline_image: bool,
+    /// Conservative EPUB that `ebook-convert` turns into AZW3 without warnings.
+    pub kindle_compatible: bool,
+    /// `"per_chapter"` (default) or `"end_of_book"` for a single Notes chapter.
+    pub footnote_placement: Option<String>,
+    /// Title of combined EPUBs in place of the publication title, e.g. for a themed
+    /// compilation. File names and per-post EPUBs are unaffected.
+    pub book_title_override: Option<String>,
+    /// Author of combined EPUBs in place of the publication author.
+    pub book_author_override: Option<String>,
+    /// `"year"`, `"month"` or `"section"` puts a divider page before each group of
+    /// chapters in combined EPUBs and nests them under it in the TOC; `"none"` by default.
+    pub group_by: Option<String>,
+    /// Puts the publication logo above the title on the title page, when one is found.
+    pub include_logo: bool,
+    /// Adds a `prefers-color-scheme: dark` block to chapter styles for readers that
+    /// honor it. Not applied to Kindle-compatible EPUBs.
+    pub dark_mode_css: bool,
+}
+
+/// Settings for how output files are named and rendered, shared by all formats.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OutputOptions {
+    pub filename_template: Option<String>,
+    pub dry_run: bool,
+    pub render_subtitle_heading: bool,
+    /// `"inline"` or `"footnote"` keeps link targets in TXT output; links are
+    /// reduced to their text otherwise.
+    pub plaintext_link_style: Option<String>,
+    /// Substack polls, buttons and subscribe forms: `"remove"` (default),
+    /// `"placeholder"` for a labeled line in their place, or `"keep"`.
+    pub embed_handling: Option<String>,
+    /// Curly quotes become straight ASCII quotes; otherwise the original typography is kept.
+    pub straight_quotes: bool,
+    /// Start the combined TXT with a numbered contents list and mark each post `## [n] Title`.
+    pub txt_include_toc: bool,
+    /// Quote the words before each footnote reference in the TXT footnote list.
+    pub txt_footnote_context: bool,
+    /// Bundle per-post files into one `{publication}.zip`; combined exports are left alone.
+    pub package_as_zip: bool,
+    /// Remove the loose files once they are in the ZIP.
+    pub delete_zipped_files: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -126,13 +376,127 @@ pub struct ExportJobResult {
     pub failed: Vec<ExportFailure>,
     pub output_files: Vec<String>,
     pub warnings: Vec<String>,
+    /// Words across all exported posts.
+    pub total_words: usize,
+    /// Size on disk of `output_files`; zero in a dry run.
+    pub total_files_bytes: u64,
+    pub duration_ms: u64,
 }
 
-#[derive(Debug, Clone)]
+/// One post's entry in the JSON metadata sidecar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostMetadataRecord {
+    #[serde(flatten)]
+    pub summary: PostSummary,
+    pub slug: String,
+    pub reading_time_minutes: Option<u32>,
+    pub word_count: usize,
+    pub footnotes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PostContent {
     pub summary: PostSummary,
     pub plain_text: String,
     pub epub_body: String,
+    pub footnotes: Vec<String>,
     pub reading_time_minutes: Option<u32>,
+    pub word_count: usize,
     pub summary_text: Option<String>,
+    pub paywalled: bool,
+    /// The body stops at the paywall cutoff, so only the free preview was exported.
+    #[serde(default)]
+    pub truncated: bool,
+    pub language: Option<String>,
+    pub substack_post_id: Option<u64>,
+    /// Profile photo next to the post's byline, when the page had one.
+    #[serde(default)]
+    pub author_avatar_url: Option<String>,
+    /// The whole post page as downloaded; only kept when raw HTML is exported,
+    /// and never written to the resume state.
+    #[serde(skip)]
+    pub raw_html: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn option_groups_default_when_missing() {
+        let request: ExportJobRequest = serde_json::from_value(serde_json::json!({
+            "publicationUrl": "https://example.substack.com",
+            "publicationTitle": "Example",
+            "mode": "entire_profile",
+            "selectedPostIds": [],
+            "orderMode": "date",
+            "manualOrder": [],
+            "sortDirection": "desc",
+            "formats": ["epub"],
+            "granularity": "combined",
+            "metadataFields": [],
+            "outputDir": "/tmp/out",
+            "posts": [],
+            "epub": { "coverMode": "generated" }
+        }))
+        .unwrap();
+        assert!(matches!(request.epub.cover_mode, Some(CoverMode::Generated)));
+        assert!(request.epub.custom_css.is_none());
+        assert!(!request.output.dry_run);
+    }
+
+    #[test]
+    fn legacy_top_level_settings_move_into_the_groups() {
+        let mut request: ExportJobRequest = serde_json::from_value(serde_json::json!({
+            "publicationUrl": "https://example.substack.com",
+            "publicationTitle": "Example",
+            "mode": "entire_profile",
+            "selectedPostIds": [],
+            "orderMode": "date",
+            "manualOrder": [],
+            "sortDirection": "desc",
+            "formats": ["epub"],
+            "granularity": "combined",
+            "metadataFields": [],
+            "outputDir": "/tmp/out",
+            "coverMode": "generated",
+            "customCss": "p { margin: 0; }",
+            "language": "fr",
+            "filenameTemplate": "{slug}",
+            "dryRun": true,
+            "epub": { "language": "de" }
+        }))
+        .unwrap();
+        request.apply_legacy_options();
+        assert!(matches!(request.epub.cover_mode, Some(CoverMode::Generated)));
+        assert_eq!(request.epub.custom_css.as_deref(), Some("p { margin: 0; }"));
+        assert_eq!(request.epub.language.as_deref(), Some("de"));
+        assert_eq!(request.output.filename_template.as_deref(), Some("{slug}"));
+        assert!(request.output.dry_run);
+        assert!(serde_json::to_value(&request).unwrap().get("coverMode").is_none());
+    }
+
+    #[test]
+    fn grouped_cover_mode_wins_over_the_legacy_one() {
+        let mut request: ExportJobRequest = serde_json::from_value(serde_json::json!({
+            "publicationUrl": "https://example.substack.com",
+            "publicationTitle": "Example",
+            "mode": "entire_profile",
+            "selectedPostIds": [],
+            "orderMode": "date",
+            "manualOrder": [],
+            "sortDirection": "desc",
+            "formats": ["epub"],
+            "granularity": "combined",
+            "metadataFields": [],
+            "outputDir": "/tmp/out",
+            "coverMode": "generated",
+            "epub": { "coverMode": "custom" }
+        }))
+        .unwrap();
+        request.apply_legacy_options();
+        assert!(matches!(request.epub.cover_mode, Some(CoverMode::Custom)));
+    }
 }
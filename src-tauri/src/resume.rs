@@ -0,0 +1,163 @@
+use crate::cache::cache_key;
+use crate::models::PostContent;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const STATE_DIR_NAME: &str = ".export-state";
+
+/// Progress of an export job, persisted in the output directory when the job
+/// resumes, so an interrupted job can be picked up without downloading everything
+/// again. Each post has its own file, so saving after a post only writes that post.
+#[derive(Debug, Clone, Default)]
+pub struct ExportState {
+    posts: HashMap<String, ResumeEntry>,
+    /// Posts changed since the last save.
+    unsaved: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResumeEntry {
+    post_id: String,
+    content: Option<PostContent>,
+    output_files: Vec<String>,
+}
+
+impl ExportState {
+    /// Reads the state left by a previous run; missing or unreadable entries start fresh.
+    pub fn load(output_dir: &Path) -> Self {
+        let posts = fs::read_dir(state_dir(output_dir))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.ok()?.path()).ok())
+            .filter_map(|raw| serde_json::from_str::<ResumeEntry>(&raw).ok())
+            .map(|entry| (entry.post_id.clone(), entry))
+            .collect();
+        Self {
+            posts,
+            unsaved: HashSet::new(),
+        }
+    }
+
+    /// Writes the entries changed since the last save.
+    pub fn save(&mut self, output_dir: &Path) -> Result<()> {
+        if self.unsaved.is_empty() {
+            return Ok(());
+        }
+        let dir = state_dir(output_dir);
+        fs::create_dir_all(&dir).context("Failed creating export state directory.")?;
+        for post_id in std::mem::take(&mut self.unsaved) {
+            let Some(entry) = self.posts.get(&post_id) else {
+                continue;
+            };
+            let raw = serde_json::to_string(entry)?;
+            fs::write(entry_path(&dir, &post_id), raw).context("Failed writing export state file.")?;
+        }
+        Ok(())
+    }
+    pub fn cached_content(&self, post_id: &str) -> Option<&PostContent> {
+        self.posts.get(post_id)?.content.as_ref()
+    }
+
+    /// Files previously written for the post, if every one still exists, is
+    /// non-empty, and together they cover all of `extensions`.
+    pub fn completed_outputs(&self, post_id: &str, extensions: &[&str]) -> Option<&[String]> {
+        let entry = self.posts.get(post_id)?;
+        if entry.output_files.is_empty() {
+            return None;
+        }
+        let all_present = entry
+            .output_files
+            .iter()
+            .all(|file| fs::metadata(file).map(|meta| meta.len() > 0).unwrap_or(false));
+        let covers_formats = extensions.iter().all(|extension| {
+            entry
+                .output_files
+                .iter()
+                .any(|file| Path::new(file).extension().and_then(|ext| ext.to_str()) == Some(*extension))
+        });
+        (all_present && covers_formats).then_some(entry.output_files.as_slice())
+    }
+
+    pub fn record_content(&mut self, content: &PostContent) {
+        let entry = self.entry_mut(&content.summary.id);
+        entry.content = Some(content.clone());
+        entry.output_files.clear();
+    }
+
+    pub fn record_output(&mut self, post_id: &str, file: &str) {
+        let entry = self.entry_mut(post_id);
+        if !entry.output_files.iter().any(|existing| existing == file) {
+            entry.output_files.push(file.to_string());
+        }
+    }
+
+    fn entry_mut(&mut self, post_id: &str) -> &mut ResumeEntry {
+        self.unsaved.insert(post_id.to_string());
+        self.posts.entry(post_id.to_string()).or_insert_with(|| ResumeEntry {
+            post_id: post_id.to_string(),
+            ..ResumeEntry::default()
+        })
+    }
+}
+
+fn state_dir(output_dir: &Path) -> PathBuf {
+    output_dir.join(STATE_DIR_NAME)
+}
+
+fn entry_path(state_dir: &Path, post_id: &str) -> PathBuf {
+    state_dir.join(format!("{:016x}.json", cache_key(post_id)))
+}
+
+const LAST_EXPORTS_FILE_NAME: &str = "last-exports.json";
+
+/// Publish date of the newest post in each publication's last successful export,
+/// kept in the app data directory so incremental exports only pick up newer posts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastExports {
+    newest_post_dates: HashMap<String, String>,
+}
+
+impl LastExports {
+    /// Reads the saved dates; a missing or unreadable file means nothing was exported yet.
+    pub fn load(state_dir: &Path) -> Self {
+        fs::read_to_string(state_dir.join(LAST_EXPORTS_FILE_NAME))
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        fs::create_dir_all(state_dir).context("Failed creating app data directory.")?;
+        let raw = serde_json::to_string_pretty(self)?;
+        fs::write(state_dir.join(LAST_EXPORTS_FILE_NAME), raw).context("Failed writing last export dates.")
+    }
+
+    pub fn newest_post_date(&self, publication_url: &str) -> Option<&str> {
+        self.newest_post_dates.get(&publication_key(publication_url)).map(String::as_str)
+    }
+
+    pub fn record(&mut self, publication_url: &str, newest_post_date: &str) {
+        self.newest_post_dates
+            .insert(publication_key(publication_url), newest_post_date.to_string());
+    }
+
+    /// Forgets one publication, or every publication when `publication_url` is `None`.
+    pub fn reset(&mut self, publication_url: Option<&str>) {
+        match publication_url {
+            Some(url) => {
+                self.newest_post_dates.remove(&publication_key(url));
+            }
+            None => self.newest_post_dates.clear(),
+        }
+    }
+}
+
+/// `https://Example.substack.com/` and `https://example.substack.com` share one entry.
+fn publication_key(publication_url: &str) -> String {
+    publication_url.trim().trim_end_matches('/').to_ascii_lowercase()
+}
@@ -1,26 +1,21 @@
-use anyhow::{anyhow, Result};
+use crate::error::AppError;
+use anyhow::Result;
 use base64::Engine;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use std::borrow::Cow;
 
+const MAX_FILENAME_CHARS: usize = 120;
+
 pub fn normalize_publication_url(input: &str) -> Result<String> {
     let trimmed = input.trim();
     if trimmed.is_empty() {
-        return Err(anyhow!("Publication URL cannot be empty."));
+        return Err(AppError::invalid_url("Publication URL cannot be empty.").into());
     }
 
-    let candidate = if trimmed.starts_with("http://") || trimmed.starts_with("https://") {
-        trimmed.to_string()
-    } else if trimmed.contains('.') {
-        format!("https://{trimmed}")
-    } else {
-        format!("https://{trimmed}.substack.com")
-    };
-
-    let parsed = url::Url::parse(&candidate).map_err(|_| anyhow!("Invalid publication URL."))?;
+    let parsed = url::Url::parse(&with_default_scheme(trimmed)).map_err(|_| AppError::invalid_url("Invalid publication URL."))?;
     let host = parsed
         .host_str()
-        .ok_or_else(|| anyhow!("Publication URL must include a valid host."))?;
+        .ok_or_else(|| AppError::invalid_url("Publication URL must include a valid host."))?;
     let mut base = format!("{}://{}", parsed.scheme(), host);
     if let Some(port) = parsed.port() {
         base.push(':');
@@ -29,17 +24,152 @@ pub fn normalize_publication_url(input: &str) -> Result<String> {
     Ok(base)
 }
 
+fn with_default_scheme(input: &str) -> String {
+    if input.starts_with("http://") || input.starts_with("https://") {
+        input.to_string()
+    } else if input.contains('.') {
+        format!("https://{input}")
+    } else {
+        format!("https://{input}.substack.com")
+    }
+}
+
+/// Returns the canonical post URL when the user pasted a link to a single post
+/// (a `/p/<slug>` path) rather than a publication.
+pub fn single_post_url(input: &str) -> Option<String> {
+    let parsed = url::Url::parse(&with_default_scheme(input.trim())).ok()?;
+    parsed.host_str()?;
+    let segments = parsed.path_segments()?.collect::<Vec<_>>();
+    let is_post = segments
+        .windows(2)
+        .any(|pair| pair[0] == "p" && !pair[1].is_empty());
+    is_post.then(|| canonical_post_url(parsed.as_str()))
+}
+
+/// Whether the language is written right to left, judged by its primary subtag.
+pub fn is_rtl_language(tag: &str) -> bool {
+    let primary = tag.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+    matches!(
+        primary.as_str(),
+        "ar" | "arc" | "ckb" | "dv" | "fa" | "he" | "iw" | "ku" | "ps" | "sd" | "ug" | "ur" | "yi"
+    )
+}
+
+/// Removes known tracking query parameters (`utm_*`, `r`, `s`, `source`,
+/// `publication_id`) while keeping the path, other parameters and the fragment.
+pub fn strip_tracking_params(input: &str) -> String {
+    let Ok(mut parsed) = url::Url::parse(input) else {
+        return input.to_string();
+    };
+    if parsed.query().is_none() {
+        return input.to_string();
+    }
+    let kept = parsed
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    if kept.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    parsed.to_string()
+}
+
+/// The `/p/<slug>` segment of a post URL, or a slug made from the title when the
+/// URL has none (lowercase letters and digits joined by `-`).
+pub fn post_slug(url: &str, title: &str) -> String {
+    let from_url = url::Url::parse(url.trim()).ok().and_then(|parsed| {
+        let segments = parsed.path_segments()?.collect::<Vec<_>>();
+        let slug = segments.windows(2).find(|pair| pair[0] == "p" && !pair[1].is_empty())?[1];
+        Some(slug.to_string())
+    });
+    if let Some(slug) = from_url {
+        return slug;
+    }
+    let slug = title
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Canonical identity of a post URL used for deduplication: scheme, lowercased
+/// host and path without a trailing slash. Query and fragment are dropped.
+pub fn canonical_post_url(input: &str) -> String {
+    let Ok(parsed) = url::Url::parse(input.trim()) else {
+        return input.trim().trim_end_matches('/').to_string();
+    };
+    let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+    let port = parsed.port().map(|port| format!(":{port}")).unwrap_or_default();
+    let path = parsed.path().trim_end_matches('/');
+    format!("{}://{host}{port}{path}", parsed.scheme())
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.starts_with("utm_") || matches!(key.as_str(), "r" | "s" | "source" | "publication_id")
+}
+
+/// Zone-less timestamps that Substack pages sometimes use; read as UTC.
+const NAIVE_DATETIME_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S"];
+/// Date-only formats; the time defaults to midnight UTC.
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%b %d, %Y", "%B %d, %Y", "%d %B %Y"];
+
+/// Parses RFC 3339 and RFC 2822 timestamps plus the looser date formats found
+/// on post pages (`2024-01-15`, `Jan 15, 2024`, `January 15, 2024`).
 pub fn parse_datetime_flexible(value: &str) -> Option<DateTime<Utc>> {
-    DateTime::parse_from_rfc3339(value)
-        .map(|v| v.with_timezone(&Utc))
-        .or_else(|_| DateTime::parse_from_rfc2822(value).map(|v| v.with_timezone(&Utc)))
-        .ok()
+    let value = value.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value).or_else(|_| DateTime::parse_from_rfc2822(value)) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    if let Some(naive) = NAIVE_DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    {
+        return Some(naive.and_utc());
+    }
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|naive| naive.and_utc())
+}
+
+/// Normalizes a BCP 47-ish language tag (`en_US` -> `en-US`) and rejects values
+/// that cannot be a language tag.
+pub fn normalize_language_tag(input: &str) -> Option<String> {
+    let candidate = input.trim().replace('_', "-");
+    let mut parts = candidate.split('-');
+    let primary = parts.next()?;
+    if !(2..=8).contains(&primary.len()) || !primary.chars().all(|ch| ch.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut normalized = primary.to_ascii_lowercase();
+    for part in parts {
+        if part.is_empty() || part.len() > 8 || !part.chars().all(|ch| ch.is_ascii_alphanumeric()) {
+            return None;
+        }
+        normalized.push('-');
+        normalized.push_str(part);
+    }
+    Some(normalized)
 }
 
+/// Turns a title into a safe file stem on every platform: Unicode letters and
+/// digits are kept, anything else becomes `_`, and Windows device names such as
+/// `CON` or `LPT1` get a leading underscore.
 pub fn sanitize_filename(input: &str) -> String {
     let mut result = String::with_capacity(input.len());
     for ch in input.chars() {
-        if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == ' ' {
+        if ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == ' ' {
             result.push(ch);
         } else {
             result.push('_');
@@ -49,20 +179,39 @@ pub fn sanitize_filename(input: &str) -> String {
     while clean.contains("  ") {
         clean = clean.replace("  ", " ");
     }
-    clean = clean.trim_matches('.').to_string();
+    let clean = clean
+        .chars()
+        .take(MAX_FILENAME_CHARS)
+        .collect::<String>()
+        .trim_matches(|ch: char| ch == '.' || ch.is_whitespace())
+        .to_string();
     if clean.is_empty() {
         "untitled".to_string()
+    } else if is_windows_reserved_name(&clean) {
+        format!("_{clean}")
     } else {
-        clean.chars().take(120).collect()
+        clean
     }
 }
 
+fn is_windows_reserved_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    if matches!(upper.as_str(), "CON" | "PRN" | "AUX" | "NUL") {
+        return true;
+    }
+    ["COM", "LPT"].iter().any(|prefix| {
+        upper
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.len() == 1 && matches!(rest.as_bytes()[0], b'1'..=b'9'))
+    })
+}
+
 pub fn decode_data_url(data_url: &str) -> Result<(Vec<u8>, String)> {
     let (meta, body) = data_url
         .split_once(',')
-        .ok_or_else(|| anyhow!("Invalid data URL format."))?;
+        .ok_or_else(|| AppError::invalid_input("Invalid data URL format."))?;
     if !meta.ends_with(";base64") {
-        return Err(anyhow!("Only base64 data URLs are supported."));
+        return Err(AppError::invalid_input("Only base64 data URLs are supported.").into());
     }
     let mime_type = meta
         .strip_prefix("data:")
@@ -72,7 +221,7 @@ pub fn decode_data_url(data_url: &str) -> Result<(Vec<u8>, String)> {
         .to_string();
     let bytes = base64::engine::general_purpose::STANDARD
         .decode(body)
-        .map_err(|_| anyhow!("Failed to decode base64 cover image."))?;
+        .map_err(|_| AppError::invalid_input("Failed to decode base64 cover image."))?;
     Ok((bytes, mime_type))
 }
 
@@ -87,8 +236,11 @@ pub fn media_type_to_extension(media_type: &str) -> &'static str {
     }
 }
 
+/// Escapes the five predefined entities and drops characters XML 1.0 does not
+/// allow at all (control characters other than tab, newline and carriage return,
+/// and U+FFFE/U+FFFF), which make readers reject the whole document.
 pub fn escape_xml(value: &str) -> Cow<'_, str> {
-    if !(value.contains('&') || value.contains('<') || value.contains('>') || value.contains('"') || value.contains('\'')) {
+    if !value.chars().any(|ch| matches!(ch, '&' | '<' | '>' | '"' | '\'') || !is_xml_char(ch)) {
         return Cow::Borrowed(value);
     }
     let mut out = String::with_capacity(value.len() + 8);
@@ -99,8 +251,93 @@ pub fn escape_xml(value: &str) -> Cow<'_, str> {
             '>' => out.push_str("&gt;"),
             '"' => out.push_str("&quot;"),
             '\'' => out.push_str("&apos;"),
+            _ if !is_xml_char(ch) => {}
             _ => out.push(ch),
         }
     }
     Cow::Owned(out)
 }
+
+fn is_xml_char(ch: char) -> bool {
+    matches!(ch, '\t' | '\n' | '\r') || (ch >= ' ' && !matches!(ch, '\u{fffe}' | '\u{ffff}'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracking_params_are_stripped_but_fragment_kept() {
+        assert_eq!(
+            strip_tracking_params("https://example.substack.com/p/foo?utm_source=x&r=abc&s=w&page=2#footnote-1"),
+            "https://example.substack.com/p/foo?page=2#footnote-1"
+        );
+        assert_eq!(
+            strip_tracking_params("https://example.substack.com/p/foo?utm_campaign=post&publication_id=12"),
+            "https://example.substack.com/p/foo"
+        );
+        assert_eq!(strip_tracking_params("not a url"), "not a url");
+    }
+
+    #[test]
+    fn canonical_post_url_ignores_case_slashes_and_query() {
+        let expected = "https://example.substack.com/p/foo";
+        assert_eq!(canonical_post_url("https://Example.Substack.com/p/foo/"), expected);
+        assert_eq!(canonical_post_url("https://example.substack.com/p/foo?utm_source=x#top"), expected);
+    }
+
+    #[test]
+    fn post_slug_comes_from_url_or_title() {
+        assert_eq!(post_slug("https://example.substack.com/p/my-essay?utm_source=x", "Ignored"), "my-essay");
+        assert_eq!(post_slug("https://example.com/p/my-essay/comments", "Ignored"), "my-essay");
+        assert_eq!(post_slug("https://example.com/archive", "Café: Notes, Part 2"), "café-notes-part-2");
+        assert_eq!(post_slug("not a url", "!!!"), "untitled");
+    }
+
+    #[test]
+    fn sanitize_filename_keeps_unicode_and_avoids_reserved_names() {
+        assert_eq!(sanitize_filename("東京の夏: Café notes"), "東京の夏_ Café notes");
+        assert_eq!(sanitize_filename("con"), "_con");
+        assert_eq!(sanitize_filename("LPT9"), "_LPT9");
+        assert_eq!(sanitize_filename("COM10"), "COM10");
+        let long = "é".repeat(200);
+        assert_eq!(sanitize_filename(&long).chars().count(), 120);
+    }
+
+    #[test]
+    fn escape_xml_drops_characters_xml_forbids() {
+        assert_eq!(escape_xml("a\u{0}b\u{8}\u{b}\u{c}c\u{ffff}"), "abc");
+        assert_eq!(escape_xml("tab\tline\r\n<ok>"), "tab\tline\r\n&lt;ok&gt;");
+        assert!(matches!(escape_xml("plain text"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn single_post_url_requires_post_path() {
+        assert_eq!(
+            single_post_url("pub.substack.com/p/my-article?utm_source=share").as_deref(),
+            Some("https://pub.substack.com/p/my-article")
+        );
+        assert_eq!(single_post_url("https://pub.substack.com/archive"), None);
+        assert_eq!(single_post_url("pub"), None);
+    }
+
+    #[test]
+    fn parses_loose_publication_dates() {
+        let utc = |value: &str| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc);
+        let cases = [
+            ("2024-01-15T08:30:00-05:00", utc("2024-01-15T13:30:00Z")),
+            ("Mon, 15 Jan 2024 13:30:00 GMT", utc("2024-01-15T13:30:00Z")),
+            ("2024-01-15T13:30:00.000", utc("2024-01-15T13:30:00Z")),
+            ("2024-01-15", utc("2024-01-15T00:00:00Z")),
+            (" Jan 15, 2024 ", utc("2024-01-15T00:00:00Z")),
+            ("Sep 5, 2023", utc("2023-09-05T00:00:00Z")),
+            ("January 15, 2024", utc("2024-01-15T00:00:00Z")),
+            ("15 January 2024", utc("2024-01-15T00:00:00Z")),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_datetime_flexible(input), Some(expected), "{input}");
+        }
+        assert_eq!(parse_datetime_flexible("yesterday"), None);
+        assert_eq!(parse_datetime_flexible("2024-13-40"), None);
+    }
+}
@@ -1,8 +1,10 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
 import { ChangeEvent, useEffect, useMemo, useState } from "react";
 import GbaScene from "./GbaScene";
 import type {
+  AppError,
   CoverMode,
   Format,
   ExportJobRequest,
@@ -11,6 +13,7 @@ import type {
   Granularity,
   MetadataField,
   PostSummary,
+  PublicationCheck,
   PublicationInfo,
   PublicationResponse,
   SortDirection,
@@ -25,10 +28,16 @@ const METADATA_OPTIONS: Array<{ key: MetadataField; label: string }> = [
   { key: "title", label: "Title" },
   { key: "author", label: "Author" },
   { key: "publishedAt", label: "Publication date" },
-  { key: "url", label: "Canonical URL" },
+  { key: "url", label: "Post URL" },
+  { key: "slug", label: "URL slug" },
+  { key: "canonicalUrl", label: "Canonical URL" },
+  { key: "section", label: "Section" },
   { key: "tags", label: "Tags" },
   { key: "subtitle", label: "Subtitle" },
   { key: "readingTime", label: "Reading time" },
+  { key: "wordCount", label: "Word count" },
+  { key: "likes", label: "Likes" },
+  { key: "commentCount", label: "Comment count" },
   { key: "summary", label: "Summary" },
 ];
 
@@ -39,6 +48,28 @@ const DEFAULT_SETTINGS: UserDefaults = {
   metadataFields: ["title", "author", "publishedAt", "url", "tags", "subtitle", "summary"],
 };
 
+function isAppError(error: unknown): error is AppError {
+  return typeof error === "object" && error !== null && "kind" in error && "message" in error;
+}
+
+function describeError(error: unknown): string {
+  if (!isAppError(error)) {
+    return error instanceof Error ? error.message : String(error);
+  }
+  switch (error.kind) {
+    case "invalidUrl":
+      return `That doesn't look like a Substack URL. ${error.message}`;
+    case "network":
+      return `Network problem, check your connection. ${error.message}`;
+    case "notFound":
+      return `Nothing found. ${error.message}`;
+    case "parse":
+      return `Substack returned something unexpected. ${error.message}`;
+    default:
+      return error.message;
+  }
+}
+
 function loadDefaults(): UserDefaults {
   try {
     const raw = localStorage.getItem(STORAGE_KEY);
@@ -87,6 +118,16 @@ function formatDate(value: string): string {
   return Number.isNaN(parsed.getTime()) ? value : parsed.toLocaleString();
 }
 
+function formatExportSummary(result: ExportJobResult): string {
+  const compact = new Intl.NumberFormat(undefined, { notation: "compact", maximumFractionDigits: 1 });
+  const megabytes = result.totalFilesBytes / (1024 * 1024);
+  const seconds = Math.round(result.durationMs / 1000);
+  const duration = seconds >= 60 ? `${Math.floor(seconds / 60)}m${seconds % 60}s` : `${seconds}s`;
+  return `Exported ${result.succeeded.length} posts, ${compact.format(result.totalWords)} words, ${
+    megabytes >= 1 ? `${megabytes.toFixed(1)} MB` : `${Math.ceil(result.totalFilesBytes / 1024)} KB`
+  } in ${duration}`;
+}
+
 function isDesktopRuntime(): boolean {
   return typeof window !== "undefined" && Boolean(window.__TAURI_INTERNALS__);
 }
@@ -100,7 +141,9 @@ export default function App() {
   const [publication, setPublication] = useState<PublicationInfo | null>(null);
   const [posts, setPosts] = useState<PostSummary[]>([]);
   const [loadingPosts, setLoadingPosts] = useState(false);
+  const [publicationCheck, setPublicationCheck] = useState<PublicationCheck | null>(null);
   const [errorText, setErrorText] = useState<string | null>(null);
+  const [retryAction, setRetryAction] = useState<(() => void) | null>(null);
 
   const [mode, setMode] = useState<ExportMode>("entire_profile");
   const [sortDirection, setSortDirection] = useState<SortDirection>("desc");
@@ -210,13 +253,19 @@ export default function App() {
 
   async function loadPublication() {
     setErrorText(null);
+    setRetryAction(null);
     setResult(null);
     setLoadingPosts(true);
+    let stopListening: (() => void) | null = null;
     try {
       if (!desktop) {
         throw new Error("Use the desktop .exe. Browser mode is intentionally unsupported.");
       }
-      const response = await invoke<PublicationResponse>("load_publication_posts", {
+      setPosts([]);
+      stopListening = await listen<PostSummary[]>("post-discovered", (event) => {
+        setPosts((current) => [...current, ...event.payload]);
+      });
+      const response = await invoke<PublicationResponse>("stream_publication_posts", {
         request: { url: publicationUrl.trim() },
       });
       setPublication(response.publication);
@@ -225,12 +274,34 @@ export default function App() {
       setFinalOrder([]);
       setStep("configure");
     } catch (error) {
-      setErrorText(String(error));
+      setErrorText(describeError(error));
+      setRetryAction(isAppError(error) && error.retryable ? () => loadPublication : null);
       setPublication(null);
       setPosts([]);
       setSelectedPostIds([]);
       setFinalOrder([]);
       setStep("configure");
+    } finally {
+      stopListening?.();
+      setLoadingPosts(false);
+    }
+  }
+
+  async function checkPublication() {
+    setErrorText(null);
+    setRetryAction(null);
+    setPublicationCheck(null);
+    setLoadingPosts(true);
+    try {
+      if (!desktop) {
+        throw new Error("Use the desktop .exe. Browser mode is intentionally unsupported.");
+      }
+      const check = await invoke<PublicationCheck>("verify_publication", {
+        request: { url: publicationUrl.trim() },
+      });
+      setPublicationCheck(check);
+    } catch (error) {
+      setErrorText(describeError(error));
     } finally {
       setLoadingPosts(false);
     }
@@ -274,6 +345,7 @@ export default function App() {
 
   async function runExport() {
     setErrorText(null);
+    setRetryAction(null);
     setResult(null);
     if (!desktop) {
       setErrorText("Export works only in desktop .exe mode. Web mode supports loading and arranging posts.");
@@ -309,6 +381,8 @@ export default function App() {
       publicationTitle: publication.title,
       publicationAuthor: publication.author,
       authorCoverUrl: publication.authorCoverUrl,
+      publicationLogoUrl: publication.publicationLogoUrl,
+      publicationLanguage: publication.language,
       mode,
       selectedPostIds,
       orderMode: mode === "specific_posts" ? "manual" : "date",
@@ -316,11 +390,14 @@ export default function App() {
       sortDirection,
       formats,
       granularity,
-      coverMode,
-      customCoverDataUrl,
       metadataFields,
       outputDir: outputDir.trim(),
       posts,
+      epub: {
+        coverMode,
+        customCoverDataUrl,
+        language: publication.language,
+      },
     };
 
     setExporting(true);
@@ -328,7 +405,8 @@ export default function App() {
       const exportResult = await invoke<ExportJobResult>("run_export_job", { request });
       setResult(exportResult);
     } catch (error) {
-      setErrorText(String(error));
+      setErrorText(describeError(error));
+      setRetryAction(isAppError(error) && error.retryable ? () => runExport : null);
     } finally {
       setExporting(false);
     }
@@ -357,7 +435,21 @@ export default function App() {
           <button disabled={loadingPosts || !publicationUrl.trim()} onClick={loadPublication}>
             {loadingPosts ? "Loading..." : "Load Posts"}
           </button>
+          <button disabled={loadingPosts || !publicationUrl.trim()} onClick={checkPublication}>
+            Check
+          </button>
         </div>
+        {publicationCheck && (
+          <p className="help-text">
+            {publicationCheck.reachable
+              ? `${publicationCheck.title ?? publicationCheck.url} is reachable` +
+                (publicationCheck.source
+                  ? ` | ${publicationCheck.estimatedPostCount ?? 0} recent posts via ${publicationCheck.source}`
+                  : " | no feed or archive found") +
+                (publicationCheck.customDomain ? " | custom domain" : "")
+              : `${publicationCheck.url} did not respond.`}
+          </p>
+        )}
         {publication && (
           <div className="publication-card">
             <h2>{publication.title}</h2>
@@ -485,6 +577,14 @@ export default function App() {
                 <input type="checkbox" checked={formats.includes("txt")} onChange={() => toggleFormat("txt")} />
                 TXT
               </label>
+              <label>
+                <input type="checkbox" checked={formats.includes("json")} onChange={() => toggleFormat("json")} />
+                JSON metadata
+              </label>
+              <label>
+                <input type="checkbox" checked={formats.includes("raw_html")} onChange={() => toggleFormat("raw_html")} />
+                Original HTML
+              </label>
             </div>
 
             <div>
@@ -521,6 +621,10 @@ export default function App() {
                 <input type="radio" checked={coverMode === "custom"} onChange={() => setCoverMode("custom")} />
                 Upload custom cover
               </label>
+              <label>
+                <input type="radio" checked={coverMode === "generated"} onChange={() => setCoverMode("generated")} />
+                Generate a cover from the title and author
+              </label>
               {coverMode === "custom" && (
                 <div className="row compact">
                   <input type="file" accept="image/*" onChange={handleCustomCoverChange} />
@@ -572,6 +676,11 @@ export default function App() {
               <button className="primary" disabled={exporting || posts.length === 0} onClick={runExport}>
                 {exporting ? "Exporting..." : "Run Export"}
               </button>
+              {exporting && desktop && (
+                <button type="button" onClick={() => void invoke("cancel_export")}>
+                  Cancel Export
+                </button>
+              )}
             </>
           )}
         </article>
@@ -581,12 +690,18 @@ export default function App() {
           <section className="panel error">
             <strong>Error</strong>
             <p>{errorText}</p>
+            {retryAction && (
+              <button type="button" onClick={retryAction}>
+                Retry
+              </button>
+            )}
           </section>
         )}
 
         {result && (
           <section className="panel result">
             <h3>Export Result</h3>
+            <p>{formatExportSummary(result)}</p>
             <p>Successful posts: {result.succeeded.length}</p>
             <p>Failed posts: {result.failed.length}</p>
             <p>Output files: {result.outputFiles.length}</p>
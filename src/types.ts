@@ -1,5 +1,8 @@
 export type PublicationRequest = {
   url: string;
+  substackSessionCookie?: string;
+  proxyUrl?: string;
+  requestTimeoutSecs?: number;
 };
 
 export type PublicationInfo = {
@@ -7,6 +10,8 @@ export type PublicationInfo = {
   title: string;
   author?: string;
   authorCoverUrl?: string;
+  language?: string;
+  publicationLogoUrl?: string;
 };
 
 export type PostSummary = {
@@ -19,6 +24,13 @@ export type PostSummary = {
   tags?: string[];
   subtitle?: string;
   summary?: string;
+  audioUrl?: string;
+  reactionCount?: number;
+  commentCount?: number;
+  section?: string;
+  canonicalUrl?: string;
+  readingTimeMinutes?: number;
+  contentHtml?: string;
 };
 
 export type PublicationResponse = {
@@ -26,21 +38,46 @@ export type PublicationResponse = {
   posts: PostSummary[];
 };
 
+export type PostPreview = {
+  title: string;
+  author?: string;
+  plainText: string;
+  epubBody: string;
+  footnoteCount: number;
+  truncated: boolean;
+  wordCount: number;
+};
+
+export type PublicationCheck = {
+  url: string;
+  reachable: boolean;
+  title?: string;
+  estimatedPostCount?: number;
+  customDomain: boolean;
+  source?: "feed" | "archive";
+};
+
 export type ExportMode = "entire_profile" | "specific_posts";
 export type OrderMode = "date" | "manual";
 export type SortDirection = "desc" | "asc";
-export type Format = "epub" | "txt";
+export type Format = "epub" | "txt" | "json" | "raw_html";
 export type Granularity = "per_post" | "combined";
-export type CoverMode = "substack_author" | "custom";
+export type CoverMode = "substack_author" | "custom" | "generated";
 
 export type MetadataField =
   | "title"
   | "author"
   | "publishedAt"
   | "url"
+  | "slug"
+  | "canonicalUrl"
+  | "section"
   | "tags"
   | "subtitle"
   | "readingTime"
+  | "wordCount"
+  | "likes"
+  | "commentCount"
   | "summary";
 
 export type ExportJobRequest = {
@@ -48,6 +85,8 @@ export type ExportJobRequest = {
   publicationTitle: string;
   publicationAuthor?: string;
   authorCoverUrl?: string;
+  publicationLogoUrl?: string;
+  publicationLanguage?: string;
   mode: ExportMode;
   selectedPostIds: string[];
   orderMode: OrderMode;
@@ -55,11 +94,71 @@ export type ExportJobRequest = {
   sortDirection: SortDirection;
   formats: Format[];
   granularity: Granularity;
-  coverMode: CoverMode;
-  customCoverDataUrl?: string;
   metadataFields: MetadataField[];
   outputDir: string;
   posts: PostSummary[];
+  postUrls?: string[];
+  substackSessionCookie?: string;
+  proxyUrl?: string;
+  requestTimeoutSecs?: number;
+  publishedAfter?: string;
+  publishedBefore?: string;
+  retryCount?: number;
+  retryBaseDelayMs?: number;
+  maxRequestsPerSecond?: number;
+  includeComments?: boolean;
+  resume?: boolean;
+  incremental?: boolean;
+  useCache?: boolean;
+  cacheTtlHours?: number;
+  refreshCache?: boolean;
+  useFeedContent?: boolean;
+  includeTags?: string[];
+  excludeTags?: string[];
+  titleContains?: string;
+  searchQuery?: string;
+  selectionRanges?: string;
+  minWordCount?: number;
+  dedupeContent?: boolean;
+  strictMode?: boolean;
+  epub?: EpubOptions;
+  output?: OutputOptions;
+};
+
+export type EpubOptions = {
+  coverMode?: CoverMode;
+  customCoverDataUrl?: string;
+  coverMaxDimension?: number;
+  coverBackground?: string;
+  customCss?: string;
+  language?: string;
+  includeTitlePage?: boolean;
+  readingDirection?: "ltr" | "rtl";
+  chapterOrder?: SortDirection;
+  combinedHeadingLevel?: number;
+  maxPostsPerVolume?: number;
+  maxBytesPerVolume?: number;
+  includeBylineImage?: boolean;
+  kindleCompatible?: boolean;
+  footnotePlacement?: "per_chapter" | "end_of_book";
+  bookTitleOverride?: string;
+  bookAuthorOverride?: string;
+  groupBy?: "none" | "year" | "month" | "section";
+  includeLogo?: boolean;
+  darkModeCss?: boolean;
+};
+
+export type OutputOptions = {
+  filenameTemplate?: string;
+  dryRun?: boolean;
+  renderSubtitleHeading?: boolean;
+  plaintextLinkStyle?: "inline" | "footnote";
+  embedHandling?: "remove" | "placeholder" | "keep";
+  straightQuotes?: boolean;
+  txtIncludeToc?: boolean;
+  txtFootnoteContext?: boolean;
+  packageAsZip?: boolean;
+  deleteZippedFiles?: boolean;
 };
 
 export type ExportJobResult = {
@@ -67,6 +166,24 @@ export type ExportJobResult = {
   failed: { postId: string; reason: string }[];
   outputFiles: string[];
   warnings: string[];
+  totalWords: number;
+  totalFilesBytes: number;
+  durationMs: number;
+};
+
+export type AppErrorKind =
+  | "invalidInput"
+  | "invalidUrl"
+  | "network"
+  | "notFound"
+  | "parse"
+  | "io"
+  | "unknown";
+
+export type AppError = {
+  kind: AppErrorKind;
+  message: string;
+  retryable: boolean;
 };
 
 export type UserDefaults = {